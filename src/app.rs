@@ -1,53 +1,153 @@
 use std::{
-    fs::File,
-    io::{self, Write},
-    process::{Command, Output},
-    str::from_utf8,
+    io,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver, TryRecvError},
+    thread,
+    time::Duration,
 };
 
 use crate::{
+    askpass::{self, Request},
+    cache,
     config::Config,
+    convert::{self, Conversion},
+    git,
+    git_url::GitUrl,
+    history::{HistoryState, HistoryView},
+    jobs::{JobQueue, JobStatus, JobsView, Notification},
+    layout::{self, RepoLayout},
     multi_input::{MultiInput, MultiInputState},
-    project_item::{ProjectItem, ProjectItemType},
+    naming::{self, TemplateVars},
+    ops::{self, CloneOptions, RepoStep},
+    project_item::{self, ProjectItem, ProjectItemType},
+    remotes::{RemotesState, RemotesView},
+    rewrite::{self, RemoteChange, UrlRewrite},
     screen::Screen,
+    session::{self, Session},
+    summary::{SummaryState, SummaryView},
     switch_screen::{ScreenSwitcher, ScreenSwitcherState, ScreenSwitcherStateBuilder},
+    task_view::TaskView,
+    transaction::{Step, Transaction},
+    watch::ProjectWatcher,
 };
 
 use ratatui::{
     DefaultTerminal, Frame,
-    crossterm::event::{self, Event, KeyCode, KeyEvent},
+    crossterm::event::{self, Event, KeyCode, KeyEvent, KeyModifiers},
     layout::{Constraint, Flex, Layout, Rect},
     style::{Color, Modifier, Style},
     text::Line,
-    widgets::{Block, Clear, Paragraph, Scrollbar, ScrollbarOrientation, StatefulWidget},
+    widgets::{
+        Block, Clear, Paragraph, Row, Scrollbar, ScrollbarOrientation, StatefulWidget, Table,
+        TableState, Wrap,
+    },
 };
 use tui_tree_widget::{Tree, TreeItem, TreeState};
 
+/// how long a finished job's notification is shown for.
+const NOTIFICATION_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// how often the projects are checked for changes made outside gpm while nothing else happens.
+const WATCH_INTERVAL: Duration = Duration::from_millis(250);
+
+/// how many rows page up and page down move through a table.
+const PAGE: u16 = 10;
+
+const SPARSE_PROMPT: &str = "Sparse Checkout Dirs (space separated, blank for all)";
+
 #[derive(Debug, Default)]
-pub struct App<'a> {
-    pub project_tree: Vec<TreeItem<'a, ProjectItem>>,
+pub struct App {
+    pub config: Config,
+    pub project_tree: Vec<TreeItem<'static, ProjectItem>>,
     tree_state: TreeState<ProjectItem>,
     pub app_screen: Screen,
     input_state: Option<MultiInputState>,
     screen_switch_state: Option<ScreenSwitcherState>,
-    summary_text: Vec<String>,
+    summary: Option<SummaryState>,
+    conversion: Option<Conversion>,
+    remotes_state: Option<RemotesState>,
+    remote_changes: Option<Vec<RemoteChange>>,
+    /// the row of the remote url rewrite preview that is selected.
+    remote_changes_state: TableState,
+    history_state: Option<HistoryState>,
+    jobs: JobQueue,
+    /// finished jobs that are shown until they expire.
+    notifications: Vec<Notification>,
+    /// how many lines the open job log is scrolled up from its end.
+    log_scroll: usize,
+    /// set when the user has asked to quit, while waiting for running jobs to be cancelled.
+    quitting: bool,
+    /// show the commands each operation would run instead of running them.
+    pub dry_run: bool,
+    /// where git and ssh ask for credentials, if it could be started.
+    pub askpass: Option<askpass::Server>,
+    /// the prompt from git or ssh being answered, shown on top of everything else.
+    credential_prompt: Option<(Request, MultiInputState)>,
+    /// the directory to suspend the TUI and run the shell command in, once the key that asked
+    /// for it has been handled.
+    shell_dir: Option<PathBuf>,
+    /// projects being created, which are selected once their job succeeds.
+    select_when_created: Vec<PathBuf>,
+    /// the projects being re-read in the background, which replace the tree once read.
+    forest_rebuild: Option<Receiver<Vec<TreeItem<'static, ProjectItem>>>>,
+    /// the item to select once the projects being re-read have been read.
+    select_when_read: Option<PathBuf>,
+    /// rebuilds the tree when projects are changed outside gpm, if watching could be started.
+    watcher: Option<ProjectWatcher>,
 }
 
-impl<'a> App<'a> {
-    pub fn run(&'a mut self, terminal: &mut DefaultTerminal) -> io::Result<bool> {
+impl App {
+    pub fn run(&mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
         loop {
             self.initialise_screen();
+            self.poll_jobs();
+            self.poll_forest();
+            self.poll_askpass();
+            self.poll_watcher();
+            if self.quitting && !self.jobs.is_busy() {
+                // losing what was opened is not worth failing to quit over.
+                let _ = session::save(&self.session());
+                return Ok(());
+            }
             terminal.draw(|frame| self.draw(frame))?;
+            // keep redrawing while jobs run, so their progress shows up and notifications expire.
+            let animating = self.jobs.is_busy()
+                || !self.notifications.is_empty()
+                || self.forest_rebuild.is_some();
+            let timeout = if animating {
+                Some(Duration::from_millis(100))
+            } else {
+                self.watcher.as_ref().map(|_| WATCH_INTERVAL)
+            };
+            if let Some(timeout) = timeout
+                && !event::poll(timeout)?
+            {
+                continue;
+            }
             let e = event::read()?;
-            if self.app_screen == Screen::Summary {
-                match e {
-                    Event::Key(k) => match k.code {
-                        KeyCode::Char('q') | KeyCode::Esc => {
-                            return Ok(false);
-                        }
-                        _ => return Ok(true),
-                    },
-                    _ => continue,
+            if self.credential_prompt.is_some() {
+                self.handle_credential_prompt_event(&e);
+                continue;
+            }
+            if let Event::Key(k) = e {
+                match self.app_screen {
+                    Screen::Summary => {
+                        self.handle_summary_key_event(k);
+                        continue;
+                    }
+                    Screen::Jobs => {
+                        self.handle_jobs_key_event(k);
+                        continue;
+                    }
+                    Screen::JobLog => {
+                        self.handle_job_log_key_event(k);
+                        continue;
+                    }
+                    Screen::History => {
+                        self.handle_history_key_event(k);
+                        continue;
+                    }
+                    _ => {}
                 }
             }
             let propagate = if let Some(ref mut s) = self.input_state {
@@ -61,9 +161,124 @@ impl<'a> App<'a> {
                     _ => false,
                 };
                 if should_exit {
-                    return Ok(false);
+                    self.quit();
                 }
             }
+            if let Some(dir) = self.shell_dir.take() {
+                self.run_shell(terminal, &dir)?;
+            }
+        }
+    }
+
+    /// hands the terminal to the shell command, run in `dir`, until it exits. the projects are
+    /// then re-read, since the command may have changed them, with `dir` still selected.
+    fn run_shell(&mut self, terminal: &mut DefaultTerminal, dir: &Path) -> io::Result<()> {
+        ratatui::restore();
+        let status = self.config.shell_command().current_dir(dir).status();
+        *terminal = ratatui::init();
+        terminal.clear()?;
+
+        self.refresh_forest();
+        if let Err(e) = status {
+            self.show_summary(SummaryState::new(vec![format!(
+                "Could not run the shell command in {:?}: {}",
+                dir, e
+            )]));
+        }
+        Ok(())
+    }
+
+    /// collects finished jobs, notifying the user and re-reading the projects they may have
+    /// changed, and starts queued ones.
+    fn poll_jobs(&mut self) {
+        let finished = self.jobs.poll(self.config.max_concurrent_jobs());
+        if !finished.is_empty() {
+            self.refresh_forest();
+        }
+        for n in finished.iter().filter(|n| n.success) {
+            if let Some(i) = self.select_when_created.iter().position(|p| *p == n.target) {
+                self.select_when_read = Some(self.select_when_created.remove(i));
+            }
+        }
+        self.notifications.extend(finished);
+        self.notifications
+            .retain(|n| n.at.elapsed() < NOTIFICATION_TIMEOUT);
+    }
+
+    /// starts rebuilding the parts of the tree that have changed on disk, once they have settled.
+    fn poll_watcher(&mut self) {
+        // changes wait for a rebuild that is already running, so that they are applied to the
+        // tree it produces rather than one it is about to replace.
+        if self.forest_rebuild.is_some() {
+            return;
+        }
+        let Some(changed) = self.watcher.as_mut().and_then(|w| w.poll()) else {
+            return;
+        };
+        let forest = self.project_tree.clone();
+        self.read_forest(move |config| config.refresh_forest(&forest, &changed));
+    }
+
+    /// starts rebuilding the tree when projects are changed outside gpm.
+    pub fn start_watching(&mut self) {
+        self.watcher = ProjectWatcher::new().ok();
+        self.watch_forest();
+    }
+
+    fn watch_forest(&mut self) {
+        if let Some(ref mut watcher) = self.watcher {
+            watcher.watch(&self.project_tree);
+        }
+    }
+
+    /// takes the next prompt from git or ssh, once the one before has been answered.
+    fn poll_askpass(&mut self) {
+        if self.credential_prompt.is_some() {
+            return;
+        }
+        let Some(request) = self.askpass.as_ref().and_then(|s| s.poll()) else {
+            return;
+        };
+        // the last line of the prompt is the question, anything before it is explanation, such
+        // as the fingerprint of an unknown host key.
+        let question = request
+            .prompt
+            .lines()
+            .last()
+            .unwrap_or_default()
+            .to_string();
+        let mut input = MultiInputState::new(
+            " Git Prompt (enter to answer, esc to cancel) ".to_string(),
+            vec![question],
+        );
+        if request.is_secret() {
+            input.set_secret_at(0);
+        }
+        self.credential_prompt = Some((request, input));
+    }
+
+    fn handle_credential_prompt_event(&mut self, e: &Event) {
+        let Some((_, ref mut input)) = self.credential_prompt else {
+            return;
+        };
+        if !input.handle_event(e) {
+            return;
+        }
+        let Some((request, input)) = self.credential_prompt.take() else {
+            return;
+        };
+        match e {
+            Event::Key(k) if k.code == KeyCode::Enter => request.answer(&input.get_content_at(0)),
+            _ => request.cancel(),
+        }
+    }
+
+    /// exits once running jobs have been cancelled, showing them stop in the meantime.
+    fn quit(&mut self) {
+        self.quitting = true;
+        if self.jobs.is_busy() {
+            self.jobs.cancel_all();
+            self.app_screen = Screen::Jobs;
         }
     }
 
@@ -72,50 +287,191 @@ impl<'a> App<'a> {
             Screen::Main => {
                 self.input_state = None;
                 self.screen_switch_state = None;
+                self.conversion = None;
+                self.remotes_state = None;
+                self.remote_changes = None;
             }
-            Screen::WorktreeCreate => {
+            Screen::Remotes => {
+                self.input_state = None;
+                if self.remotes_state.is_some() {
+                    return;
+                }
+                let Some(repo) = self.get_selected_pt_item() else {
+                    self.app_screen = Screen::Main;
+                    return;
+                };
+                match RemotesState::load(&repo.path) {
+                    Some(state) => self.remotes_state = Some(state),
+                    None => {
+                        self.show_summary(SummaryState::new(vec![format!(
+                            "Could not list the remotes of {:?}",
+                            repo.path
+                        )]));
+                    }
+                }
+            }
+            Screen::RemoteAdd | Screen::RemoteRename | Screen::RemoteSetUrl => {
                 if self.input_state.is_some() {
                     return;
                 }
-                self.input_state = Some(MultiInputState::new(
-                    " Create New Branch as Worktree ".to_string(),
+                let selected = self
+                    .remotes_state
+                    .as_ref()
+                    .and_then(|r| r.selected().cloned());
+                self.input_state = Some(match (self.app_screen, selected) {
+                    (Screen::RemoteRename, Some(remote)) => {
+                        let mut state = MultiInputState::new(
+                            format!(" Rename Remote {} ", remote.name),
+                            vec!["New Name".to_string()],
+                        );
+                        state.set_content_at(0, remote.name);
+                        state
+                    }
+                    (Screen::RemoteSetUrl, Some(remote)) => {
+                        let mut state = MultiInputState::new(
+                            format!(" Set URL of Remote {} ", remote.name),
+                            vec!["Remote Link".to_string()],
+                        );
+                        state.set_content_at(0, remote.url);
+                        state
+                    }
+                    (Screen::RemoteAdd, _) => MultiInputState::new(
+                        " Add Remote ".to_string(),
+                        vec!["Remote Name".to_string(), "Remote Link".to_string()],
+                    ),
+                    _ => {
+                        self.app_screen = Screen::Remotes;
+                        return;
+                    }
+                });
+            }
+            Screen::RewriteRemotes => {
+                if self.input_state.is_some() {
+                    return;
+                }
+                let mut state = MultiInputState::new(
+                    " Rewrite Remote URLs ".to_string(),
                     vec![
-                        "Branch Name".to_string(),
-                        "Directory Name (blank for default)".to_string(),
+                        format!("Mode ({})", rewrite::MODES),
+                        "Match (prefix or regex to replace, or prefix to switch)".to_string(),
+                        "Replace With (blank for to-ssh and to-https)".to_string(),
                     ],
-                ));
+                );
+                state.set_content_at(0, "prefix".to_string());
+                self.input_state = Some(state);
             }
-            Screen::WorktreeRepoCreate => {
+            Screen::ForkRepoCreate => {
                 if self.input_state.is_some() {
                     return;
                 }
                 self.input_state = Some(MultiInputState::new(
-                    " Create new Worktree Branch ".to_string(),
+                    " Checkout Fork ".to_string(),
                     vec![
-                        "Repo Link".to_string(),
+                        "Fork Link (origin)".to_string(),
+                        "Upstream Link (upstream)".to_string(),
                         "Directory Name (blank for default)".to_string(),
                     ],
                 ));
             }
-            Screen::NonWorktreeRepoCreate => {
+            Screen::ConvertToWorktreeRepo | Screen::ConvertToNormalRepo => {
+                if self.conversion.is_some() {
+                    return;
+                }
+                let Some(repo) = self.get_selected_pt_item() else {
+                    self.app_screen = Screen::Main;
+                    return;
+                };
+                let conversion = if self.app_screen == Screen::ConvertToWorktreeRepo {
+                    convert::to_worktree_repo(&repo.path, |branch| {
+                        // an unusable name is left empty, which the conversion refuses.
+                        Self::sanitise_git_dir_name(branch, false).unwrap_or_default()
+                    })
+                } else {
+                    convert::to_normal_repo(&repo.path)
+                };
+                match conversion {
+                    Ok(c) => self.conversion = Some(c),
+                    Err(e) => {
+                        self.show_summary(SummaryState::new(vec![e]));
+                    }
+                }
+            }
+            Screen::WorktreeCreate => {
                 if self.input_state.is_some() {
                     return;
                 }
-                self.input_state = Some(MultiInputState::new(
-                    " Create Non-Worktree Repo ".to_string(),
+                let Some(repo) = self.get_selected_pt_item() else {
+                    return;
+                };
+                let options = self.config.options_for(&repo.path);
+                let sparse = options.clone_options.sparse_checkout.join(" ");
+                if options.branch_template.is_none() && options.worktree_dir_template.is_none() {
+                    let mut state = MultiInputState::new(
+                        " Create New Branch as Worktree ".to_string(),
+                        vec![
+                            "Branch Name".to_string(),
+                            "Directory Name (blank for default)".to_string(),
+                            SPARSE_PROMPT.to_string(),
+                        ],
+                    );
+                    state.set_content_at(2, sparse);
+                    self.input_state = Some(state);
+                    return;
+                }
+
+                // the template is pre-filled with everything but the ticket and slug, which are
+                // substituted in when the form is submitted.
+                let vars = TemplateVars::for_repo(&repo.path);
+                let mut state = MultiInputState::new(
+                    " Create New Branch as Worktree ".to_string(),
                     vec![
-                        "Repo Link".to_string(),
+                        "Ticket ID".to_string(),
+                        "Slug".to_string(),
+                        "Branch Name".to_string(),
                         "Directory Name (blank for default)".to_string(),
+                        SPARSE_PROMPT.to_string(),
                     ],
-                ));
+                );
+                state.set_content_at(4, sparse);
+                if let Some(ref template) = options.branch_template {
+                    state.set_content_at(2, naming::render(template, &vars));
+                }
+                if let Some(ref template) = options.worktree_dir_template {
+                    state.set_content_at(3, naming::render(template, &vars));
+                }
+                self.input_state = Some(state);
+            }
+            Screen::WorktreeRepoCreate => {
+                if self.input_state.is_some() {
+                    return;
+                }
+                self.input_state = self.clone_form(" Create new Worktree Branch ", false);
+            }
+            Screen::NonWorktreeRepoCreate => {
+                if self.input_state.is_some() {
+                    return;
+                }
+                self.input_state = self.clone_form(" Create Non-Worktree Repo ", true);
             }
             _ => {}
         }
     }
 
     fn handle_key_event(&mut self, k: KeyEvent) -> bool {
+        if self.app_screen == Screen::Remotes {
+            self.handle_remotes_key_event(k);
+            return false;
+        }
         match k.code {
             KeyCode::Esc => match self.app_screen {
+                Screen::RemoteAdd
+                | Screen::RemoteRename
+                | Screen::RemoteSetUrl
+                | Screen::RemoteRemove => {
+                    self.input_state = None;
+                    self.app_screen = Screen::Remotes;
+                    return false;
+                }
                 Screen::WorktreeRepoCreate | Screen::WorktreeCreate => {
                     self.input_state = None;
                     self.app_screen = Screen::Main;
@@ -154,6 +510,7 @@ impl<'a> App<'a> {
                             s.down();
                         }
                     }
+                    Screen::RewriteRemotesPreview => self.remote_changes_state.select_next(),
                     _ => {}
                 };
                 return false;
@@ -168,13 +525,37 @@ impl<'a> App<'a> {
                             s.up();
                         }
                     }
+                    Screen::RewriteRemotesPreview => self.remote_changes_state.select_previous(),
                     _ => {}
                 };
                 return false;
             }
+            KeyCode::PageDown if self.app_screen == Screen::RewriteRemotesPreview => {
+                self.remote_changes_state.scroll_down_by(PAGE);
+            }
+            KeyCode::PageUp if self.app_screen == Screen::RewriteRemotesPreview => {
+                self.remote_changes_state.scroll_up_by(PAGE);
+            }
             KeyCode::Char(' ') => {
                 self.tree_state.toggle_selected();
             }
+            KeyCode::Char('R') if self.app_screen == Screen::Main => {
+                self.app_screen = Screen::RewriteRemotes;
+            }
+            KeyCode::Char('J') if self.app_screen == Screen::Main => {
+                self.app_screen = Screen::Jobs;
+            }
+            KeyCode::Char('H') if self.app_screen == Screen::Main => {
+                self.history_state = Some(HistoryState::load());
+                self.app_screen = Screen::History;
+            }
+            KeyCode::Char('r') if self.app_screen == Screen::Main => self.refresh_forest(),
+            KeyCode::Char('s') if self.app_screen == Screen::Main => {
+                self.shell_dir = self.get_selected_pt_item().map(|p| p.path);
+            }
+            KeyCode::Char('D') if self.app_screen == Screen::Main => {
+                self.dry_run = !self.dry_run;
+            }
             KeyCode::Char('x') => {
                 let Some(selected) = self.tree_state.selected().last() else {
                     return false;
@@ -192,12 +573,31 @@ impl<'a> App<'a> {
                 Screen::WorktreeDelete => self.delete_worktree(),
                 Screen::WorktreeRepoDelete => self.delete_repo(),
                 Screen::NonWorktreeRepoDelete => self.delete_repo(),
+                Screen::PlainDirectoryInit => self.init_plain_directory(),
+                Screen::PlainDirectoryConvert => self.convert_plain_directory(),
+                Screen::DissociateFromCache => self.dissociate_from_cache(),
+                Screen::SubmoduleUpdate => self.update_submodules(),
+                Screen::LfsFetch => self.fetch_lfs_objects(),
+                Screen::RemoteRemove => self.remove_remote(),
+                Screen::RewriteRemotesPreview => self.rewrite_remotes(),
+                Screen::ConvertToWorktreeRepo | Screen::ConvertToNormalRepo => {
+                    self.run_conversion()
+                }
                 _ => {}
             },
             KeyCode::Char('n') => match self.app_screen {
                 Screen::WorktreeDelete
                 | Screen::WorktreeRepoDelete
-                | Screen::NonWorktreeRepoDelete => self.app_screen = Screen::Main,
+                | Screen::NonWorktreeRepoDelete
+                | Screen::PlainDirectoryInit
+                | Screen::PlainDirectoryConvert
+                | Screen::DissociateFromCache
+                | Screen::SubmoduleUpdate
+                | Screen::LfsFetch
+                | Screen::ConvertToWorktreeRepo
+                | Screen::ConvertToNormalRepo
+                | Screen::RewriteRemotesPreview => self.app_screen = Screen::Main,
+                Screen::RemoteRemove => self.app_screen = Screen::Remotes,
                 _ => {}
             },
             KeyCode::Enter => {
@@ -207,37 +607,85 @@ impl<'a> App<'a> {
                 match self.app_screen {
                     Screen::Main => match selected_proj.project_type {
                         ProjectItemType::NonWorktreeRepo => {
-                            self.screen_switch_state = Some(
+                            let mut menu =
                                 ScreenSwitcherStateBuilder::new(" Project Menu ".to_string())
                                     .with_option(
-                                        "Delete Project".to_string(),
-                                        Screen::NonWorktreeRepoDelete,
+                                        "New Branch As Linked Worktree".to_string(),
+                                        Screen::WorktreeCreate,
                                     )
-                                    .build(),
+                                    .with_option(
+                                        "Convert to Worktree Repo".to_string(),
+                                        Screen::ConvertToWorktreeRepo,
+                                    );
+                            if git::has_submodules(&selected_proj.path) {
+                                menu = menu.with_option(
+                                    "Update Submodules".to_string(),
+                                    Screen::SubmoduleUpdate,
+                                );
+                            }
+                            if git::uses_lfs(&selected_proj.path) {
+                                menu = menu
+                                    .with_option("Fetch LFS Objects".to_string(), Screen::LfsFetch);
+                            }
+                            menu = menu.with_option("Remotes".to_string(), Screen::Remotes);
+                            if cache::alternates_file(&selected_proj.path).is_some() {
+                                menu = menu.with_option(
+                                    "Dissociate from Clone Cache".to_string(),
+                                    Screen::DissociateFromCache,
+                                );
+                            }
+                            self.screen_switch_state = Some(
+                                menu.with_option(
+                                    "Delete Project".to_string(),
+                                    Screen::NonWorktreeRepoDelete,
+                                )
+                                .build(),
                             );
                             self.app_screen = Screen::ScreenSwitchMenu
                         }
                         ProjectItemType::Worktree => {
+                            let mut menu =
+                                ScreenSwitcherStateBuilder::new(" Project Menu ".to_string());
+                            if git::has_submodules(&selected_proj.path) {
+                                menu = menu.with_option(
+                                    "Update Submodules".to_string(),
+                                    Screen::SubmoduleUpdate,
+                                );
+                            }
+                            if git::uses_lfs(&selected_proj.path) {
+                                menu = menu
+                                    .with_option("Fetch LFS Objects".to_string(), Screen::LfsFetch);
+                            }
                             self.screen_switch_state = Some(
-                                ScreenSwitcherStateBuilder::new(" Project Menu ".to_string())
-                                    .with_option(
-                                        "Delete Project".to_string(),
-                                        Screen::WorktreeDelete,
-                                    )
-                                    .build(),
+                                menu.with_option(
+                                    "Delete Project".to_string(),
+                                    Screen::WorktreeDelete,
+                                )
+                                .build(),
                             );
                             self.app_screen = Screen::ScreenSwitchMenu
                         }
                         ProjectItemType::WorktreeRepo => {
+                            let mut menu = ScreenSwitcherStateBuilder::new(
+                                " Project Worktree Menu ".to_string(),
+                            )
+                            .with_option(
+                                "New Branch As Worktree".to_string(),
+                                Screen::WorktreeCreate,
+                            )
+                            .with_option(
+                                "Convert to Ordinary Repo".to_string(),
+                                Screen::ConvertToNormalRepo,
+                            );
+                            menu = menu.with_option("Remotes".to_string(), Screen::Remotes);
+                            if cache::alternates_file(&selected_proj.path).is_some() {
+                                menu = menu.with_option(
+                                    "Dissociate from Clone Cache".to_string(),
+                                    Screen::DissociateFromCache,
+                                );
+                            }
                             self.screen_switch_state = Some(
-                                ScreenSwitcherStateBuilder::new(
-                                    " Project Worktree Menu ".to_string(),
-                                )
-                                .with_option(
-                                    "New Branch As Worktree".to_string(),
-                                    Screen::WorktreeCreate,
-                                )
-                                .with_option(
+                                menu.with_option(
                                     "Delete Worktree".to_string(),
                                     Screen::WorktreeRepoDelete,
                                 )
@@ -258,15 +706,49 @@ impl<'a> App<'a> {
                                     "Checkout New Repo - Non Worktree Mode".to_string(),
                                     Screen::NonWorktreeRepoCreate,
                                 )
+                                .with_option(
+                                    "Checkout Fork - Worktree Mode".to_string(),
+                                    Screen::ForkRepoCreate,
+                                )
                                 .build(),
                             );
                             self.app_screen = Screen::ScreenSwitchMenu
                         }
+                        ProjectItemType::Group => {
+                            self.tree_state.toggle_selected();
+                        }
+                        ProjectItemType::PlainDirectory => {
+                            self.screen_switch_state = Some(
+                                ScreenSwitcherStateBuilder::new(" Directory Menu ".to_string())
+                                    .with_option(
+                                        "Initialise as Git Repo".to_string(),
+                                        Screen::PlainDirectoryInit,
+                                    )
+                                    .with_option(
+                                        "Convert to Worktree Repo".to_string(),
+                                        Screen::PlainDirectoryConvert,
+                                    )
+                                    .build(),
+                            );
+                            self.app_screen = Screen::ScreenSwitchMenu
+                        }
                     },
                     Screen::WorktreeCreate => {
                         self.checkout_new_worktree();
                         return false;
                     }
+                    Screen::ForkRepoCreate => {
+                        self.checkout_fork();
+                        return false;
+                    }
+                    Screen::RewriteRemotes => {
+                        self.plan_remote_rewrite();
+                        return false;
+                    }
+                    Screen::RemoteAdd | Screen::RemoteRename | Screen::RemoteSetUrl => {
+                        self.edit_remote();
+                        return false;
+                    }
                     Screen::WorktreeRepoCreate => {
                         self.checkout_new_worktree_repo();
                         return false;
@@ -286,35 +768,62 @@ impl<'a> App<'a> {
             }
             _ => return false,
         };
-        return false;
+        false
     }
 
     fn draw(&mut self, frame: &mut Frame) {
+        self.draw_screen(frame);
+
+        let Some((ref request, ref mut input)) = self.credential_prompt else {
+            return;
+        };
+        let mut explanation: Vec<Line> = request.prompt.lines().map(Line::raw).collect();
+        explanation.pop();
+        let explanation_height = if explanation.is_empty() {
+            0
+        } else {
+            explanation.len() as u16 + 2
+        };
+        let pop_area = popup_list(frame.area(), 70, explanation_height + 3);
+        let [explanation_area, input_area] = Layout::vertical([
+            Constraint::Length(explanation_height),
+            Constraint::Length(5),
+        ])
+        .areas(pop_area);
+        frame.render_widget(Clear, pop_area);
+        if !explanation.is_empty() {
+            let paragraph = Paragraph::new(explanation)
+                .wrap(Wrap { trim: false })
+                .block(Block::bordered());
+            frame.render_widget(paragraph, explanation_area);
+        }
+        MultiInput {}.render(input_area, frame.buffer_mut(), input);
+    }
+
+    fn draw_screen(&mut self, frame: &mut Frame) {
         let area = frame.area();
         if self.app_screen == Screen::Summary {
-            frame.render_widget(Clear, area);
-            let summary_area = popup_inputs(area, 90, 90);
-            let mut lines = vec![Line::styled(
-                "Execution Summary",
-                Style::default().add_modifier(Modifier::BOLD),
-            )];
-            for l in self.summary_text.iter() {
-                lines.push(Line::default());
-                lines.push(Line::raw(l.clone()))
+            if let Some(ref mut state) = self.summary {
+                frame.render_widget(Clear, area);
+                SummaryView {}.render(popup_inputs(area, 90, 90), frame.buffer_mut(), state);
             }
-            lines.push(Line::styled(
-                "Press q / Escape to exit, or any other key to continue editing.",
-                Style::default().add_modifier(Modifier::BOLD),
-            ));
-            let paragraph = Paragraph::new(lines).centered().block(Block::bordered());
-
-            frame.render_widget(paragraph, summary_area);
             return;
         }
 
+        let mut title = "Projects".to_string();
+        if self.dry_run {
+            title += " [DRY RUN - D to turn off]";
+        }
+        if self.jobs.is_busy() {
+            title += &format!(
+                " ({} running, {} queued - J for jobs)",
+                self.jobs.count(JobStatus::Running),
+                self.jobs.count(JobStatus::Queued)
+            );
+        }
         let widget = Tree::new(&self.project_tree)
             .expect("all item identifiers are unique")
-            .block(Block::bordered().title("Projects"))
+            .block(Block::bordered().title(title))
             .experimental_scrollbar(Some(
                 Scrollbar::new(ScrollbarOrientation::VerticalRight)
                     .begin_symbol(None)
@@ -346,6 +855,145 @@ impl<'a> App<'a> {
                 frame.render_widget(Clear, pop_area);
                 frame.render_widget(paragraph, pop_area);
             }
+            Screen::PlainDirectoryInit
+            | Screen::PlainDirectoryConvert
+            | Screen::DissociateFromCache
+            | Screen::SubmoduleUpdate
+            | Screen::LfsFetch => {
+                let prompt = match self.app_screen {
+                    Screen::PlainDirectoryInit => "Initialise as Git Repo [Y/n]?",
+                    Screen::PlainDirectoryConvert => "Convert to Worktree Repo [Y/n]?",
+                    Screen::DissociateFromCache => "Dissociate from Clone Cache [Y/n]?",
+                    Screen::SubmoduleUpdate => "Update Submodules [Y/n]?",
+                    Screen::LfsFetch => "Fetch LFS Objects [Y/n]?",
+                    _ => unreachable!(),
+                };
+                let paragraph = Paragraph::new(prompt).centered().block(Block::bordered());
+                let pop_area = popup_list(area, 35, 1);
+
+                frame.render_widget(Clear, pop_area);
+                frame.render_widget(paragraph, pop_area);
+            }
+            Screen::Remotes => {
+                if let Some(ref mut state) = self.remotes_state {
+                    let pop_area = popup_list(area, 70, state.get_line_count() as u16);
+                    frame.render_widget(Clear, pop_area);
+                    RemotesView {}.render(pop_area, frame.buffer_mut(), state);
+                }
+            }
+            Screen::Jobs => {
+                let pop_area = popup_inputs(area, 80, 60);
+                frame.render_widget(Clear, pop_area);
+                JobsView {}.render(pop_area, frame.buffer_mut(), &mut self.jobs);
+            }
+            Screen::JobLog => {
+                if let Some(job) = self.jobs.selected_mut()
+                    && let Some(ref mut task) = job.task
+                {
+                    let pop_area = popup_inputs(area, 90, 80);
+                    frame.render_widget(Clear, pop_area);
+                    TaskView {
+                        scroll: self.log_scroll,
+                        summary: &job.summary,
+                    }
+                    .render(pop_area, frame.buffer_mut(), task);
+                }
+            }
+            Screen::History => {
+                if let Some(ref mut state) = self.history_state {
+                    let pop_area = popup_inputs(area, 90, 80);
+                    frame.render_widget(Clear, pop_area);
+                    HistoryView {}.render(pop_area, frame.buffer_mut(), state);
+                }
+            }
+            Screen::RewriteRemotesPreview => {
+                if let Some(ref changes) = self.remote_changes {
+                    let selected = self
+                        .remote_changes_state
+                        .selected()
+                        .unwrap_or_default()
+                        .min(changes.len().saturating_sub(1));
+                    let rows = changes.iter().map(|c| {
+                        let remote = if c.push {
+                            format!("{} (push)", c.remote)
+                        } else {
+                            c.remote.clone()
+                        };
+                        Row::new(vec![
+                            c.repo.file_name().unwrap().to_string_lossy().into_owned(),
+                            remote,
+                            c.old_url.clone(),
+                            c.new_url.clone(),
+                        ])
+                    });
+                    let table = Table::new(
+                        rows,
+                        [
+                            Constraint::Percentage(20),
+                            Constraint::Percentage(10),
+                            Constraint::Percentage(35),
+                            Constraint::Percentage(35),
+                        ],
+                    )
+                    .header(
+                        Row::new(vec!["Repo", "Remote", "Old URL", "New URL"])
+                            .style(Style::default().add_modifier(Modifier::BOLD)),
+                    )
+                    .row_highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+                    .block(
+                        Block::bordered()
+                            .title(format!(
+                                " Rewrite {} Remote URLs (row {} of {}) ",
+                                changes.len(),
+                                selected + 1,
+                                changes.len()
+                            ))
+                            .title_bottom(" j/k scroll | Apply these changes [Y/n]? "),
+                    );
+                    let pop_area = popup_inputs(area, 90, 80);
+                    frame.render_widget(Clear, pop_area);
+                    frame.render_stateful_widget(table, pop_area, &mut self.remote_changes_state);
+                }
+            }
+            Screen::RemoteRemove => {
+                let name = self
+                    .remotes_state
+                    .as_ref()
+                    .and_then(|r| r.selected())
+                    .map(|r| r.name.clone())
+                    .unwrap_or_default();
+                let paragraph = Paragraph::new(format!("Remove Remote {} [Y/n]?", name))
+                    .centered()
+                    .block(Block::bordered());
+                let pop_area = popup_list(area, 35, 1);
+
+                frame.render_widget(Clear, pop_area);
+                frame.render_widget(paragraph, pop_area);
+            }
+            Screen::ConvertToWorktreeRepo | Screen::ConvertToNormalRepo => {
+                if let Some(ref conversion) = self.conversion {
+                    let mut lines = vec![
+                        Line::styled(
+                            conversion.title.clone(),
+                            Style::default().add_modifier(Modifier::BOLD),
+                        ),
+                        Line::default(),
+                    ];
+                    lines.extend(conversion.preview().into_iter().map(Line::raw));
+                    lines.push(Line::default());
+                    lines.push(Line::styled(
+                        "Run these steps [Y/n]?",
+                        Style::default().add_modifier(Modifier::BOLD),
+                    ));
+                    let paragraph = Paragraph::new(lines)
+                        .wrap(Wrap { trim: false })
+                        .block(Block::bordered().title(" Preview "));
+                    let pop_area = popup_inputs(area, 80, 60);
+
+                    frame.render_widget(Clear, pop_area);
+                    frame.render_widget(paragraph, pop_area);
+                }
+            }
             Screen::ScreenSwitchMenu => {
                 if let Some(ref mut state) = self.screen_switch_state {
                     let w = ScreenSwitcher::new();
@@ -353,40 +1001,192 @@ impl<'a> App<'a> {
                     w.render(area, frame.buffer_mut(), state);
                 }
             }
-            Screen::WorktreeRepoCreate | Screen::WorktreeCreate | Screen::NonWorktreeRepoCreate => {
+            Screen::WorktreeRepoCreate
+            | Screen::WorktreeCreate
+            | Screen::NonWorktreeRepoCreate
+            | Screen::ForkRepoCreate
+            | Screen::RewriteRemotes
+            | Screen::RemoteAdd
+            | Screen::RemoteRename
+            | Screen::RemoteSetUrl => {
                 if let Some(ref mut state) = self.input_state {
                     let w = MultiInput {};
-                    let pop_area = popup_inputs(area, 50, 20);
+                    // leave a blank row above and below the boxes.
+                    let height = state.get_box_count() as u16 * 3 + 2;
+                    let pop_area = popup_list(area, 50, height);
                     w.render(pop_area, frame.buffer_mut(), state);
                 }
             }
             _ => {}
         }
+
+        self.draw_notifications(frame);
+    }
+
+    /// stacks the notifications of recently finished jobs in the bottom right corner.
+    fn draw_notifications(&self, frame: &mut Frame) {
+        let area = frame.area();
+        let width = area.width.min(60);
+        let mut bottom = area.bottom().saturating_sub(1);
+        for n in self.notifications.iter().rev() {
+            if bottom < area.top() + 3 {
+                break;
+            }
+            let pop_area = Rect::new(area.right().saturating_sub(width + 1), bottom - 3, width, 3);
+            let colour = if n.success {
+                Color::LightGreen
+            } else {
+                Color::LightRed
+            };
+            let paragraph = Paragraph::new(n.text.clone())
+                .block(Block::bordered().border_style(Style::new().fg(colour)));
+            frame.render_widget(Clear, pop_area);
+            frame.render_widget(paragraph, pop_area);
+            bottom -= 3;
+        }
     }
 
     fn get_selected_pt_item(&self) -> Option<ProjectItem> {
         self.tree_state.selected().last().cloned()
     }
 
+    /// starts re-reading the projects in the background, so that the tree can still be used while
+    /// large project directories are read.
+    fn refresh_forest(&mut self) {
+        self.read_forest(|config| config.to_forest());
+    }
+
+    /// builds the tree with `read` on another thread. it replaces the tree once `poll_forest`
+    /// finds it has finished.
+    fn read_forest(
+        &mut self,
+        read: impl FnOnce(Config) -> Vec<TreeItem<'static, ProjectItem>> + Send + 'static,
+    ) {
+        let config = self.config.clone();
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let _ = tx.send(read(config));
+        });
+        // a rebuild that was already running is dropped, as it may have missed changes.
+        self.forest_rebuild = Some(rx);
+    }
+
+    /// replaces the tree once the projects have been re-read, keeping the same items opened and
+    /// selected.
+    fn poll_forest(&mut self) {
+        let Some(ref rx) = self.forest_rebuild else {
+            return;
+        };
+        let forest = match rx.try_recv() {
+            Ok(forest) => forest,
+            Err(TryRecvError::Empty) => return,
+            Err(TryRecvError::Disconnected) => {
+                self.forest_rebuild = None;
+                return;
+            }
+        };
+        self.forest_rebuild = None;
+        let session = self.session();
+        self.project_tree = forest;
+        self.restore_session(session);
+        if let Some(path) = self.select_when_read.take() {
+            self.select_path(&path);
+        }
+        self.watch_forest();
+    }
+
+    /// what is opened and selected in the tree.
+    fn session(&self) -> Session {
+        Session {
+            opened: self
+                .tree_state
+                .opened()
+                .iter()
+                .filter_map(|id| id.last().map(|item| item.path.clone()))
+                .collect(),
+            selected: self.get_selected_pt_item().map(|item| item.path),
+        }
+    }
+
+    /// opens and selects the items in `session` that are still in the tree. if the selected
+    /// item has gone, the closest item containing it is selected instead.
+    pub fn restore_session(&mut self, session: Session) {
+        self.tree_state.close_all();
+        for path in session.opened.iter() {
+            if let Some(identifier) = find_item(&self.project_tree, &|item| item.path == *path) {
+                self.tree_state.open(identifier);
+            }
+        }
+        match session.selected {
+            Some(path) => self.select_path(&path),
+            None => {
+                self.tree_state.select(vec![]);
+            }
+        }
+    }
+
+    /// selects the item for `path` in the tree, opening the items above it. if it is no longer
+    /// there, e.g. because it was deleted, the closest item containing it is selected instead.
+    fn select_path(&mut self, path: &Path) {
+        let Some(identifier) = find_path(&self.project_tree, path) else {
+            return;
+        };
+        for i in 1..identifier.len() {
+            self.tree_state.open(identifier[..i].to_vec());
+        }
+        self.tree_state.select(identifier);
+    }
+
+    /// the repo that the selected item is nested under, skipping any groups.
+    fn get_selected_pt_owner(&self) -> Option<ProjectItem> {
+        let selected = self.tree_state.selected();
+        selected
+            .iter()
+            .rev()
+            .skip(1)
+            .find(|p| {
+                p.project_type == ProjectItemType::WorktreeRepo
+                    || p.project_type == ProjectItemType::NonWorktreeRepo
+            })
+            .cloned()
+    }
+
     fn delete_worktree(&mut self) {
         let Some(wt) = self.get_selected_pt_item() else {
             return;
         };
         let wt_name = wt.path.file_name().unwrap();
-        let output = Command::new("git")
-            .current_dir(wt.path.parent().unwrap())
-            .arg("worktree")
-            .arg("remove")
-            .arg(wt_name)
-            .output()
-            .expect("Failed to start 'git' process");
-
-        self.generate_cmd_summary(
-            &format!("Deleting Worktree {}", wt_name.to_string_lossy()),
-            output,
-        );
+        // worktrees are nested under the repo that owns them, which is not necessarily their
+        // parent directory on disk.
+        let owner_path = match self.get_selected_pt_owner() {
+            Some(owner) => match RepoLayout::detect(&owner.path) {
+                Some(layout) => layout.bare_dir(&owner.path),
+                None => owner.path,
+            },
+            None => wt.path.parent().unwrap().to_path_buf(),
+        };
+        // only the worktree groups directly above it, stopping at the repo that owns it, so that
+        // the repo and the host/owner groups it is in are never touched.
+        let groups: Vec<PathBuf> = self
+            .tree_state
+            .selected()
+            .iter()
+            .rev()
+            .skip(1)
+            .take_while(|p| p.project_type == ProjectItemType::Group)
+            .map(|p| p.path.clone())
+            .collect();
+        let desc = format!("Deleting Worktree {}", wt_name.to_string_lossy());
 
-        self.app_screen = Screen::Summary;
+        let mut steps = vec![RepoStep::git(
+            &owner_path,
+            &["worktree", "remove", &wt.path.to_string_lossy()],
+        )];
+        // clean up group directories left empty by removing a nested worktree.
+        if !groups.is_empty() {
+            steps.push(RepoStep::RemoveEmptyDirs(groups));
+        }
+        self.queue_transaction(Ok(Transaction::new(desc, wt.path, steps)));
     }
 
     fn delete_repo(&mut self) {
@@ -395,239 +1195,744 @@ impl<'a> App<'a> {
         };
         let repo_name = repo.path.file_name().unwrap();
 
-        let output = Command::new("rm")
-            .current_dir(&repo.path.parent().unwrap())
-            .arg("-rf")
-            .arg(repo_name)
-            .output()
-            .expect("Failed to start 'rm' process");
+        let desc = format!("Deleting Repo {}", repo_name.to_string_lossy());
+
+        let steps = layout::repo_delete_dirs(&repo.path)
+            .into_iter()
+            .map(RepoStep::RemoveDir)
+            .collect();
+        self.queue_transaction(Ok(Transaction::new(desc, repo.path.clone(), steps)));
+    }
 
-        self.generate_cmd_summary(
-            &format!("Deleting Repo {}", repo_name.to_string_lossy()),
-            output,
+    fn init_plain_directory(&mut self) {
+        let Some(dir) = self.get_selected_pt_item() else {
+            return;
+        };
+
+        let desc = format!(
+            "Initialising git repo in {}",
+            dir.path.file_name().unwrap().to_string_lossy()
         );
 
-        self.app_screen = Screen::Summary;
+        self.queue_transaction(Ok(Transaction::new(
+            desc,
+            dir.path.clone(),
+            vec![RepoStep::git(&dir.path, &["init"])],
+        )));
     }
 
-    fn checkout_new_worktree_repo(&mut self) {
-        if let Some(ref i_state) = self.input_state {
-            let repo_link = i_state.get_content_at(0);
-            let chosen_repo_name = i_state.get_content_at(1);
-            let repo_dir_name = Self::sanitise_git_dir_name(if chosen_repo_name.is_empty() {
-                match Self::get_repo_name_from_git_link(&repo_link) {
-                    Ok(name) => name,
-                    Err(e) => {
-                        self.summary_text = vec![e];
-                        self.app_screen = Screen::Summary;
-                        return;
-                    }
+    fn convert_plain_directory(&mut self) {
+        let Some(dir) = self.get_selected_pt_item() else {
+            return;
+        };
+
+        let is_empty = dir
+            .path
+            .read_dir()
+            .map(|mut contents| contents.next().is_none())
+            .unwrap_or(false);
+        if !is_empty {
+            self.show_summary(SummaryState::new(vec![format!(
+                "Cannot convert {:?} to a worktree repo as it is not empty. Initialise it as a git repo instead.",
+                dir.path
+            )]));
+            return;
+        }
+
+        // a sibling layout repo keeps its bare repo next to the directory, which then holds the
+        // worktrees.
+        let layout = self.config.options_for(&dir.path).layout;
+        let bare_dir = match layout {
+            // appended rather than set as the extension, so that e.g. `v1.2` becomes `v1.2.git`.
+            RepoLayout::Sibling => {
+                let mut name = dir.path.clone().into_os_string();
+                name.push(".git");
+                PathBuf::from(name)
+            }
+            _ => layout.bare_dir(&dir.path),
+        };
+        if bare_dir.exists() {
+            self.show_summary(SummaryState::new(vec![format!(
+                "Cannot convert {:?} to a worktree repo as {:?} already exists.",
+                dir.path, bare_dir
+            )]));
+            return;
+        }
+
+        let title = format!("Converting {:?} to a worktree repo", dir.path);
+        let target = dir.path.clone();
+        let mut steps = vec![RepoStep::InitBare {
+            cwd: dir.path.clone(),
+            git_dir: bare_dir,
+        }];
+        if layout == RepoLayout::DotBare {
+            steps.push(RepoStep::WriteGitdirFile(dir.path));
+        }
+        self.queue_transaction(Ok(Transaction::new(title, target, steps)));
+    }
+
+    /// queues `work` to run in the background, and goes back to the projects while it does. `keys`
+    /// are the directories it changes, so that it waits for other jobs changing them.
+    fn queue_job(
+        &mut self,
+        title: String,
+        target: PathBuf,
+        keys: Vec<PathBuf>,
+        work: impl Fn() -> Result<Vec<String>, Vec<String>> + Send + Sync + 'static,
+    ) {
+        self.jobs.push(title, target, keys, work);
+        self.input_state = None;
+        self.app_screen = Screen::Main;
+    }
+
+    /// queues a transaction that creates a project, which is selected once it has been created.
+    fn queue_create<S: Step + Send + Sync + 'static>(
+        &mut self,
+        plan: Result<Transaction<S>, String>,
+    ) {
+        if let Ok(ref transaction) = plan
+            && !self.dry_run
+        {
+            self.select_when_created.push(transaction.target.clone());
+        }
+        self.queue_transaction(plan);
+    }
+
+    /// queues a planned transaction to run in the background, or shows why it could not be
+    /// planned. in dry-run mode the commands it would run are shown instead.
+    fn queue_transaction<S: Step + Send + Sync + 'static>(
+        &mut self,
+        plan: Result<Transaction<S>, String>,
+    ) {
+        match plan {
+            Ok(transaction) if self.dry_run => {
+                self.input_state = None;
+                self.show_summary(SummaryState::new(transaction.dry_run()));
+            }
+            Ok(transaction) => self.queue_job(
+                transaction.title.clone(),
+                transaction.target.clone(),
+                transaction.keys(),
+                move || transaction.run(),
+            ),
+            Err(e) => self.show_summary(SummaryState::new(vec![e])),
+        }
+    }
+
+    fn handle_jobs_key_event(&mut self, k: KeyEvent) {
+        match k.code {
+            KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('J') => {
+                self.app_screen = Screen::Main
+            }
+            KeyCode::Char('j') | KeyCode::Down => self.jobs.down(),
+            KeyCode::Char('k') | KeyCode::Up => self.jobs.up(),
+            KeyCode::Char('r') => self.jobs.retry_selected(),
+            KeyCode::Char('c') => self.jobs.cancel_selected(),
+            KeyCode::Char('C') => self.jobs.clear_finished(),
+            KeyCode::Enter => match self.jobs.selected() {
+                Some(job) if matches!(job.status, JobStatus::Succeeded | JobStatus::Failed) => {
+                    self.show_job_summary()
                 }
-            } else {
-                &chosen_repo_name
-            });
+                Some(job) if job.task.is_some() => {
+                    self.log_scroll = 0;
+                    self.app_screen = Screen::JobLog;
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+    }
 
-            let Some(dir) = self.get_selected_pt_item() else {
-                return;
-            };
+    /// shows the summary of the selected job, which has finished.
+    fn show_job_summary(&mut self) {
+        let Some(job) = self.jobs.selected() else {
+            return;
+        };
+        let commands = job
+            .task
+            .as_ref()
+            .map(|t| t.commands.clone())
+            .unwrap_or_default();
+        let summary = SummaryState::new(job.summary.clone())
+            .with_commands(commands)
+            .with_target(job.target.clone())
+            .retryable(job.status == JobStatus::Failed)
+            .back_to(Screen::Jobs);
+        self.show_summary(summary);
+    }
 
-            let mkdir_output = Command::new("mkdir")
-                .current_dir(&dir.path)
-                .arg(&repo_dir_name)
-                .output()
-                .expect("Failed to start 'mkdir' process.");
+    fn show_summary(&mut self, summary: SummaryState) {
+        self.summary = Some(summary);
+        self.app_screen = Screen::Summary;
+    }
 
-            if !mkdir_output.status.success() {
-                self.generate_cmd_summary(
-                    &format!("Mkdir {} at {:?}", &repo_dir_name, dir.path),
-                    mkdir_output,
-                );
-                return;
+    fn handle_summary_key_event(&mut self, k: KeyEvent) {
+        let Some(ref mut state) = self.summary else {
+            self.app_screen = Screen::Main;
+            return;
+        };
+        state.clear_message();
+        match k.code {
+            KeyCode::Esc | KeyCode::Char('q') => {
+                let back = state.back;
+                self.summary = None;
+                if back == Screen::Main {
+                    self.refresh_forest();
+                }
+                self.app_screen = back;
+            }
+            KeyCode::Char('j') | KeyCode::Down => state.scroll_down(1),
+            KeyCode::Char('k') | KeyCode::Up => state.scroll_up(1),
+            KeyCode::PageDown => state.page_down(),
+            KeyCode::PageUp => state.page_up(),
+            KeyCode::Tab => state.next_command(),
+            KeyCode::BackTab => state.previous_command(),
+            KeyCode::Enter | KeyCode::Char(' ') => state.toggle_selected(),
+            KeyCode::Char('c') => state.copy(),
+            KeyCode::Char('r') if state.retryable => {
+                // a retryable summary is only shown for the selected job.
+                self.jobs.retry_selected();
+                self.summary = None;
+                self.app_screen = Screen::Jobs;
             }
+            KeyCode::Char('o') => {
+                if let Some(target) = state.target.clone() {
+                    self.summary = None;
+                    self.select_path(&target);
+                    self.refresh_forest();
+                    self.select_when_read = Some(target);
+                    self.app_screen = Screen::Main;
+                }
+            }
+            _ => {}
+        }
+    }
 
-            let mut repo_path = dir.path.clone();
-            repo_path.push(&repo_dir_name);
+    fn handle_job_log_key_event(&mut self, k: KeyEvent) {
+        let Some(job) = self.jobs.selected() else {
+            self.app_screen = Screen::Jobs;
+            return;
+        };
+        let line_count = job.task.as_ref().map_or(0, |t| t.log.len()) + job.summary.len();
+        match k.code {
+            KeyCode::Esc | KeyCode::Char('q') => self.app_screen = Screen::Jobs,
+            KeyCode::Char('c') if k.modifiers.contains(KeyModifiers::CONTROL) => {
+                self.jobs.cancel_selected()
+            }
+            KeyCode::Char('r') if job.status == JobStatus::Failed => {
+                self.jobs.retry_selected();
+                self.app_screen = Screen::Jobs;
+            }
+            KeyCode::Char('k') | KeyCode::Up if self.log_scroll + 1 < line_count => {
+                self.log_scroll += 1;
+            }
+            KeyCode::Char('j') | KeyCode::Down => {
+                self.log_scroll = self.log_scroll.saturating_sub(1);
+            }
+            _ => {}
+        }
+    }
 
-            let clone_output = Command::new("git")
-                .current_dir(&repo_path)
-                .arg("clone")
-                .arg("--bare")
-                .arg(&repo_link)
-                .arg(".bare")
-                .output()
-                .expect("Failed to start 'git clone' process");
+    fn handle_history_key_event(&mut self, k: KeyEvent) {
+        let Some(ref mut state) = self.history_state else {
+            self.app_screen = Screen::Main;
+            return;
+        };
+        if state.filtering {
+            match k.code {
+                KeyCode::Esc | KeyCode::Enter => state.filtering = false,
+                KeyCode::Backspace => state.pop_filter(),
+                KeyCode::Char(c) => state.push_filter(c),
+                _ => {}
+            }
+            return;
+        }
+        match k.code {
+            KeyCode::Esc | KeyCode::Char('q') => {
+                self.history_state = None;
+                self.app_screen = Screen::Main;
+            }
+            KeyCode::Char('j') | KeyCode::Down => state.down(),
+            KeyCode::Char('k') | KeyCode::Up => state.up(),
+            KeyCode::Char('/') => state.filtering = true,
+            KeyCode::Enter => {
+                if let Some(entry) = state.selected() {
+                    let summary = SummaryState::new(entry.details())
+                        .with_commands(entry.commands.clone())
+                        .with_target(entry.target.clone())
+                        .back_to(Screen::History);
+                    self.show_summary(summary);
+                }
+            }
+            _ => {}
+        }
+    }
 
-            if !clone_output.status.success() {
-                self.generate_cmd_summary("git clone", clone_output);
-                return;
+    fn handle_remotes_key_event(&mut self, k: KeyEvent) {
+        let Some(ref mut state) = self.remotes_state else {
+            self.app_screen = Screen::Main;
+            return;
+        };
+        let has_selection = state.selected().is_some();
+        match k.code {
+            KeyCode::Esc | KeyCode::Char('q') => self.app_screen = Screen::Main,
+            KeyCode::Char('j') | KeyCode::Down => state.down(),
+            KeyCode::Char('k') | KeyCode::Up => state.up(),
+            KeyCode::Char('a') => self.app_screen = Screen::RemoteAdd,
+            KeyCode::Char('d') if has_selection => self.app_screen = Screen::RemoteRemove,
+            KeyCode::Char('r') if has_selection => self.app_screen = Screen::RemoteRename,
+            KeyCode::Char('u') if has_selection => self.app_screen = Screen::RemoteSetUrl,
+            KeyCode::Char('b') if has_selection => {
+                self.set_remote_role("checkout.defaultRemote");
+            }
+            KeyCode::Char('p') if has_selection => {
+                self.set_remote_role("remote.pushDefault");
             }
+            _ => {}
+        }
+    }
 
-            {
-                let mut file_path = repo_path.clone();
-                file_path.push(".git");
-                let Ok(mut f) = File::create(&file_path) else {
-                    self.summary_text =
-                        vec![format!("Failed to create .git file at {:?}", &file_path)];
-                    self.app_screen = Screen::Summary;
-                    return;
-                };
-                if let Err(e) = f.write_all(b"gitdir: ./.bare") {
-                    self.summary_text = vec![
-                        format!("Failed to write to .git file at {:?}", &file_path),
-                        format!("Error: {}", e),
-                    ];
-                    self.app_screen = Screen::Summary;
-                    return;
-                };
+    /// queues setting the config `key` of the repo to the selected remote.
+    fn set_remote_role(&mut self, key: &str) {
+        let Some(ref state) = self.remotes_state else {
+            return;
+        };
+        let Some(remote) = state.selected() else {
+            return;
+        };
+        let repo = state.repo.clone();
+        self.queue_transaction(Ok(Transaction::new(
+            format!("Setting {} to {}", key, remote.name),
+            repo.clone(),
+            vec![RepoStep::git(&repo, &["config", key, &remote.name])],
+        )));
+    }
+
+    /// adds, renames or sets the url of a remote from the submitted remote form.
+    fn edit_remote(&mut self) {
+        let (Some(i_state), Some(state)) = (&self.input_state, &self.remotes_state) else {
+            return;
+        };
+        let repo = state.repo.clone();
+        let selected = state.selected().map(|r| r.name.clone()).unwrap_or_default();
+        let (title, args): (String, Vec<String>) = match self.app_screen {
+            Screen::RemoteAdd => {
+                let name = i_state.get_content_at(0).trim().to_string();
+                let link = self.config.expand_clone_link(&i_state.get_content_at(1));
+                (
+                    format!("Adding remote {}", name),
+                    vec!["remote".into(), "add".into(), name, link],
+                )
+            }
+            Screen::RemoteRename => {
+                let name = i_state.get_content_at(0).trim().to_string();
+                (
+                    format!("Renaming remote {}", selected),
+                    vec!["remote".into(), "rename".into(), selected, name],
+                )
             }
+            Screen::RemoteSetUrl => {
+                let link = self.config.expand_clone_link(&i_state.get_content_at(0));
+                (
+                    format!("Setting the url of remote {}", selected),
+                    vec!["remote".into(), "set-url".into(), selected, link],
+                )
+            }
+            _ => return,
+        };
 
-            let config_output = Command::new("git")
-                .current_dir(&repo_path)
-                .arg("config")
-                .arg("remote.origin.fetch")
-                .arg("+refs/heads/*:refs/remotes/origin/*")
-                .output()
-                .expect("Failed to start 'git config' process");
+        let args: Vec<&str> = args.iter().map(|a| a.as_str()).collect();
+        let mut steps = vec![RepoStep::git(&repo, &args)];
+        // a new remote is fetched straight away so its branches can be checked out.
+        if let ["remote", "add", name, _] = args[..] {
+            steps.push(RepoStep::git(&repo, &["fetch", name]));
+        }
+        self.queue_transaction(Ok(Transaction::new(title, repo, steps)));
+    }
 
-            if !config_output.status.success() {
-                self.generate_cmd_summary("git config", config_output);
+    fn remove_remote(&mut self) {
+        let Some(ref state) = self.remotes_state else {
+            return;
+        };
+        let Some(remote) = state.selected() else {
+            return;
+        };
+        let repo = state.repo.clone();
+        let name = remote.name.clone();
+        self.queue_transaction(Ok(Transaction::new(
+            format!("Removing remote {}", name),
+            repo.clone(),
+            vec![RepoStep::git(&repo, &["remote", "remove", &name])],
+        )));
+    }
+
+    /// works out which remote urls the submitted rewrite form changes, for the preview.
+    fn plan_remote_rewrite(&mut self) {
+        let Some(ref i_state) = self.input_state else {
+            return;
+        };
+        let rewrite = match UrlRewrite::new(
+            &i_state.get_content_at(0),
+            &i_state.get_content_at(1),
+            &i_state.get_content_at(2),
+        ) {
+            Ok(rewrite) => rewrite,
+            Err(e) => {
+                self.show_summary(SummaryState::new(vec![e]));
                 return;
             }
+        };
 
-            let fetch_output = Command::new("git")
-                .current_dir(&repo_path)
-                .arg("fetch")
-                .arg("origin")
-                .output()
-                .expect("failed to start 'git fetch' process");
+        let changes = rewrite::plan(&project_item::forest_repos(&self.project_tree), &rewrite);
+        if changes.is_empty() {
+            self.show_summary(SummaryState::new(vec![
+                "No remote urls matched.".to_string(),
+            ]));
+            return;
+        }
+        self.input_state = None;
+        self.remote_changes = Some(changes);
+        self.remote_changes_state = TableState::new().with_selected(0);
+        self.app_screen = Screen::RewriteRemotesPreview;
+    }
 
-            if !fetch_output.status.success() {
-                self.generate_cmd_summary("git config", config_output);
+    fn rewrite_remotes(&mut self) {
+        let Some(changes) = self.remote_changes.take() else {
+            return;
+        };
+        self.queue_transaction(Ok(rewrite::to_transaction(&changes)));
+    }
+
+    fn update_submodules(&mut self) {
+        let Some(worktree) = self.get_selected_pt_item() else {
+            return;
+        };
+        self.queue_transaction(Ok(Transaction::new(
+            "Updating submodules".to_string(),
+            worktree.path.clone(),
+            vec![ops::submodule_step(&worktree.path)],
+        )));
+    }
+
+    fn fetch_lfs_objects(&mut self) {
+        let Some(worktree) = self.get_selected_pt_item() else {
+            return;
+        };
+        self.queue_transaction(Ok(Transaction::new(
+            "Fetching LFS objects".to_string(),
+            worktree.path.clone(),
+            vec![RepoStep::FetchLfs(worktree.path)],
+        )));
+    }
+
+    fn dissociate_from_cache(&mut self) {
+        let Some(repo) = self.get_selected_pt_item() else {
+            return;
+        };
+        self.queue_transaction(cache::plan_dissociate(&repo.path));
+    }
+
+    fn run_conversion(&mut self) {
+        let Some(conversion) = self.conversion.take() else {
+            return;
+        };
+        self.queue_transaction(Ok(conversion));
+    }
+
+    fn checkout_new_worktree_repo(&mut self) {
+        let Some((parent, repo_dir_name, repo_link)) = self.clone_target() else {
+            return;
+        };
+        let Some(clone_options) = self.clone_options_from_form() else {
+            return;
+        };
+        let layout = self.config.options_for(&parent).layout;
+        self.queue_create(ops::plan_worktree_clone(
+            layout,
+            &parent,
+            &repo_dir_name,
+            &repo_link,
+            &clone_options,
+        ));
+    }
+
+    fn checkout_new_non_worktree_repo(&mut self) {
+        let Some((parent, repo_dir_name, repo_link)) = self.clone_target() else {
+            return;
+        };
+        let Some(clone_options) = self.clone_options_from_form() else {
+            return;
+        };
+        let plan =
+            ops::plan_non_worktree_clone(&parent, &repo_dir_name, &repo_link, &clone_options).map(
+                |mut plan| {
+                    if self.config.init_submodules_for(&parent) {
+                        plan.push(ops::submodule_step(&parent.join(&repo_dir_name)));
+                    }
+                    plan
+                },
+            );
+        self.queue_create(plan);
+    }
+
+    fn checkout_fork(&mut self) {
+        let Some(ref i_state) = self.input_state else {
+            return;
+        };
+        let upstream_link = self.config.expand_clone_link(&i_state.get_content_at(1));
+        let Some(dir) = self.get_selected_pt_item() else {
+            return;
+        };
+        // the fork and upstream usually share a name, so the upstream decides the placement.
+        let chosen_repo_name = i_state.get_content_at(2);
+        let fork_link = self.config.expand_clone_link(&i_state.get_content_at(0));
+        let target = if chosen_repo_name.is_empty() {
+            Self::get_repo_name_from_git_link(&upstream_link)
+        } else {
+            Ok(chosen_repo_name)
+        }
+        .and_then(|repo_name| {
+            let parent = self.config.clone_parent(&dir.path, &upstream_link)?;
+            Ok((parent, Self::sanitise_git_dir_name(&repo_name, false)?))
+        });
+        let (parent, repo_dir_name) = match target {
+            Ok(target) => target,
+            Err(e) => {
+                self.show_summary(SummaryState::new(vec![e]));
                 return;
             }
+        };
+
+        let options = self.config.options_for(&parent);
+        self.queue_create(ops::plan_fork_clone(
+            options.layout,
+            &parent,
+            &repo_dir_name,
+            &fork_link,
+            &upstream_link,
+            &options.clone_options,
+        ));
+    }
 
-            self.summary_text = vec![format!(
-                "Checked out new repo with name {} {:?}",
-                repo_dir_name, &repo_path
-            )];
+    /// builds a clone form, pre-filled with the clone options of the selected project directory.
+    /// the sparse-checkout box is only shown for ordinary clones.
+    fn clone_form(&self, title: &str, sparse: bool) -> Option<MultiInputState> {
+        let dir = self.get_selected_pt_item()?;
+        let defaults = self.config.options_for(&dir.path).clone_options;
 
-            self.app_screen = Screen::Summary;
+        let mut prompts = vec![
+            "Repo Link".to_string(),
+            "Directory Name (blank for default)".to_string(),
+            "Partial Clone Filter (e.g. blob:none, blank for none)".to_string(),
+            "Depth (blank for full history)".to_string(),
+            "Single Branch [y/N]".to_string(),
+        ];
+        if sparse {
+            prompts.push(SPARSE_PROMPT.to_string());
+        }
+        let mut state = MultiInputState::new(title.to_string(), prompts);
+        state.set_content_at(2, defaults.filter.unwrap_or_default());
+        state.set_content_at(3, defaults.depth.map(|d| d.to_string()).unwrap_or_default());
+        state.set_content_at(
+            4,
+            if defaults.single_branch { "y" } else { "n" }.to_string(),
+        );
+        if sparse {
+            state.set_content_at(5, defaults.sparse_checkout.join(" "));
         }
+        Some(state)
     }
 
-    fn checkout_new_non_worktree_repo(&mut self) {
-        if let Some(ref i_state) = self.input_state {
-            let repo_link = i_state.get_content_at(0);
-            let chosen_repo_name = &i_state.get_content_at(1);
-            let repo_name = Self::sanitise_git_dir_name(if chosen_repo_name.is_empty() {
-                match Self::get_repo_name_from_git_link(&repo_link) {
-                    Ok(name) => name,
-                    Err(e) => {
-                        self.summary_text = vec![e];
-                        self.app_screen = Screen::Summary;
-                        return;
-                    }
+    /// reads the clone options from a clone form. errors are put on the summary screen.
+    fn clone_options_from_form(&mut self) -> Option<CloneOptions> {
+        let i_state = self.input_state.as_ref()?;
+        let dir = self.get_selected_pt_item()?;
+        let defaults = self.config.options_for(&dir.path).clone_options;
+        let filter = i_state.get_content_at(2).trim().to_string();
+        let depth = i_state.get_content_at(3).trim().to_string();
+        let depth = if depth.is_empty() {
+            None
+        } else {
+            match depth.parse::<u32>() {
+                Ok(d) if d > 0 => Some(d),
+                _ => {
+                    self.show_summary(SummaryState::new(vec![format!(
+                        "'{}' is not a valid clone depth.",
+                        depth
+                    )]));
+                    return None;
                 }
-            } else {
-                &chosen_repo_name
-            });
+            }
+        };
+        let sparse_checkout = if i_state.get_box_count() > 5 {
+            Self::sparse_patterns_from(&i_state.get_content_at(5))
+        } else {
+            vec![]
+        };
 
-            let Some(repo_dir) = self.get_selected_pt_item() else {
-                return;
-            };
+        Some(CloneOptions {
+            filter: (!filter.is_empty()).then_some(filter),
+            depth,
+            single_branch: i_state
+                .get_content_at(4)
+                .trim()
+                .to_lowercase()
+                .starts_with('y'),
+            sparse_checkout,
+            reference_cache: defaults.reference_cache,
+            lfs_skip_smudge: defaults.lfs_skip_smudge,
+        })
+    }
 
-            let output = Command::new("git")
-                .current_dir(&repo_dir.path)
-                .arg("clone")
-                .arg(repo_link)
-                .arg(&repo_name)
-                .output()
-                .expect("Failed to start 'git' process");
-
-            self.generate_cmd_summary(
-                &format!("Checking out new Non-Worktree Repo {}", repo_name),
-                output,
-            );
+    fn sparse_patterns_from(s: &str) -> Vec<String> {
+        s.split_whitespace().map(|p| p.to_string()).collect()
+    }
+
+    /// reads the clone form, returning the directory to clone into, the repo directory name and
+    /// the expanded repo link. errors are put on the summary screen.
+    fn clone_target(&mut self) -> Option<(PathBuf, String, String)> {
+        let i_state = self.input_state.as_ref()?;
+        let repo_link = self.config.expand_clone_link(&i_state.get_content_at(0));
+        let chosen_repo_name = i_state.get_content_at(1);
+        let dir = self.get_selected_pt_item()?;
+
+        let target = if chosen_repo_name.is_empty() {
+            Self::get_repo_name_from_git_link(&repo_link)
+        } else {
+            Ok(chosen_repo_name)
+        }
+        .and_then(|repo_name| {
+            let parent = self.config.clone_parent(&dir.path, &repo_link)?;
+            Ok((parent, Self::sanitise_git_dir_name(&repo_name, false)?))
+        });
 
-            self.app_screen = Screen::Summary;
+        match target {
+            Ok((parent, repo_dir_name)) => Some((parent, repo_dir_name, repo_link)),
+            Err(e) => {
+                self.show_summary(SummaryState::new(vec![e]));
+                None
+            }
         }
     }
 
     fn checkout_new_worktree(&mut self) {
         if let Some(ref i_state) = self.input_state {
-            let branch_name = i_state.get_content_at(0);
-            let chosen_dir_name = &i_state.get_content_at(1);
-            let dir_name = Self::sanitise_git_dir_name(if chosen_dir_name.is_empty() {
-                &branch_name
-            } else {
-                &chosen_dir_name
-            });
-
             let Some(repo) = self.get_selected_pt_item() else {
                 return;
             };
+            let options = self.config.options_for(&repo.path);
+
+            let (branch_name, chosen_dir_name) =
+                if options.branch_template.is_none() && options.worktree_dir_template.is_none() {
+                    (i_state.get_content_at(0), i_state.get_content_at(1))
+                } else {
+                    let vars = TemplateVars {
+                        ticket: Some(i_state.get_content_at(0)),
+                        slug: Some(i_state.get_content_at(1)),
+                        ..TemplateVars::for_repo(&repo.path)
+                    };
+                    (
+                        naming::render(&i_state.get_content_at(2), &vars),
+                        naming::render(&i_state.get_content_at(3), &vars),
+                    )
+                };
+            let chosen_dir_name = &chosen_dir_name;
+
+            if let Err(e) = naming::check_branch_name(&branch_name) {
+                self.show_summary(SummaryState::new(vec![e]));
+                return;
+            }
+
+            let dir_name = match Self::sanitise_git_dir_name(
+                if chosen_dir_name.is_empty() {
+                    &branch_name
+                } else {
+                    chosen_dir_name
+                },
+                options.nested_worktree_dirs && RepoLayout::detect(&repo.path).is_some(),
+            ) {
+                Ok(dir_name) => dir_name,
+                Err(e) => {
+                    self.show_summary(SummaryState::new(vec![e]));
+                    return;
+                }
+            };
+
+            // ordinary clones keep their linked worktrees next to them rather than inside.
+            let (git_dir, worktree_path) = match RepoLayout::detect(&repo.path) {
+                Some(layout) => {
+                    let root = layout.worktree_root(&repo.path, options.worktree_subdir.as_deref());
+                    (layout.bare_dir(&repo.path), root.join(&dir_name))
+                }
+                None => {
+                    let repo_name = repo.path.file_name().unwrap().to_string_lossy();
+                    let worktree_path = repo
+                        .path
+                        .parent()
+                        .unwrap()
+                        .join(format!("{}-{}", repo_name, dir_name));
+                    (repo.path.clone(), worktree_path)
+                }
+            };
+
+            let sparse_patterns =
+                Self::sparse_patterns_from(&i_state.get_content_at(i_state.get_box_count() - 1));
+
+            // with a base remote set, the branch starts from that remote rather than whichever
+            // remote git guesses.
+            let start_args = match git::base_start_point(&git_dir, &branch_name) {
+                Some((start, true)) => vec!["--track".to_string(), start],
+                Some((start, false)) => vec!["--no-track".to_string(), start],
+                None => vec!["--guess-remote".to_string()],
+            };
 
-            let output = Command::new("git")
-                .current_dir(&repo.path)
-                .arg("worktree")
-                .arg("add")
-                .arg("-b")
-                .arg(branch_name)
-                .arg(&dir_name)
-                .arg("--guess-remote")
-                .output()
-                .expect("Failed to start 'git' process");
-
-            self.generate_cmd_summary(
-                &format!(
+            let mut plan = ops::plan_worktree_add(
+                format!(
                     "Checking out new Worktree {} in repo {}",
                     dir_name,
                     repo.path.file_name().unwrap().to_string_lossy()
                 ),
-                output,
+                &git_dir,
+                &worktree_path,
+                &branch_name,
+                start_args,
+                &sparse_patterns,
+                &options.clone_options,
             );
-
-            self.app_screen = Screen::Summary;
+            if self.config.init_submodules_for(&repo.path) {
+                plan.push(ops::submodule_step(&worktree_path));
+            }
+            self.queue_create(Ok(plan));
         }
     }
 
-    fn sanitise_git_dir_name(s: &str) -> String {
-        s.replace("/", "_") // slashes will break the structure this project scans for dirs for.
-            .replace(".", "_") // git doesnt like dots in dir paths
-    }
-
-    fn generate_cmd_summary(&mut self, desc: &str, output: Output) {
-        if output.status.success() {
-            self.summary_text = vec![format!("SUCCESS: {}", desc)];
-        } else {
-            self.summary_text = vec![
-                format!("FAILURE: {}", desc),
-                format!(
-                    "STDOUT: {}",
-                    from_utf8(&output.stdout).unwrap_or("couldnt read stdout as utf-8")
-                ),
-                format!(
-                    "STDERR: {}",
-                    from_utf8(&output.stderr).unwrap_or("couldnt read stderr as utf-8")
-                ),
-            ];
+    /// turns a branch or repo name into a directory name. slashes become nested directories if
+    /// `nested` is set, and are flattened to underscores otherwise. dots are kept, except where git
+    /// would reject the path component (`..`, a leading dot or a `.lock` suffix). fails if
+    /// nothing is left, as the directory would then be the one it was meant to be created in.
+    fn sanitise_git_dir_name(s: &str, nested: bool) -> Result<String, String> {
+        let components: Vec<String> = s
+            .split('/')
+            .filter(|c| !c.is_empty())
+            .map(|c| {
+                let mut c = c.replace("..", "__");
+                if c.starts_with('.') {
+                    c.replace_range(..1, "_");
+                }
+                if let Some(stripped) = c.strip_suffix(".lock") {
+                    c = format!("{}_lock", stripped);
+                }
+                c
+            })
+            .collect();
+        if components.is_empty() {
+            return Err(format!("{:?} cannot be used as a directory name.", s));
         }
+        Ok(components.join(if nested { "/" } else { "_" }))
     }
 
-    fn get_repo_name_from_git_link(s: &str) -> Result<&str, String> {
-        let Some((_, after_slash)) = s.rsplit_once('/') else {
-            return Err(format!(
-                "Could not interpret '{}' as git repository link.",
-                s
-            ));
-        };
-        let Some((default_name, _)) = after_slash.rsplit_once('.') else {
-            return Err(format!(
-                "Could not interpret '{}' as git repository link.",
-                s
-            ));
-        };
-        Ok(default_name)
+    fn get_repo_name_from_git_link(s: &str) -> Result<String, String> {
+        GitUrl::parse(s).map(|url| url.repo_name().to_string())
     }
 }
 
@@ -646,3 +1951,98 @@ fn popup_inputs(area: Rect, percent_x: u16, percent_y: u16) -> Rect {
     let [area] = horizontal.areas(area);
     area
 }
+
+/// the identifier of the item for `path`, or failing that of the deepest item containing it.
+fn find_path(items: &[TreeItem<ProjectItem>], path: &Path) -> Option<Vec<ProjectItem>> {
+    find_item(items, &|item| item.path == path)
+        .or_else(|| find_item(items, &|item| path.starts_with(&item.path)))
+}
+
+/// the identifier of the deepest item matching `matches`.
+fn find_item(
+    items: &[TreeItem<ProjectItem>],
+    matches: &dyn Fn(&ProjectItem) -> bool,
+) -> Option<Vec<ProjectItem>> {
+    items.iter().find_map(|item| {
+        let id = item.identifier();
+        match find_item(item.children(), matches) {
+            Some(mut below) => {
+                below.insert(0, id.clone());
+                Some(below)
+            }
+            None => matches(id).then(|| vec![id.clone()]),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flattens_slashes_unless_nested() {
+        assert_eq!(
+            App::sanitise_git_dir_name("feature/login", false).unwrap(),
+            "feature_login"
+        );
+        assert_eq!(
+            App::sanitise_git_dir_name("feature/login", true).unwrap(),
+            "feature/login"
+        );
+    }
+
+    #[test]
+    fn keeps_dots_git_allows() {
+        assert_eq!(
+            App::sanitise_git_dir_name("v1.2-fix", false).unwrap(),
+            "v1.2-fix"
+        );
+        assert_eq!(
+            App::sanitise_git_dir_name("repo.rs", true).unwrap(),
+            "repo.rs"
+        );
+    }
+
+    #[test]
+    fn replaces_dots_git_rejects() {
+        assert_eq!(App::sanitise_git_dir_name("a..b", false).unwrap(), "a__b");
+        assert_eq!(
+            App::sanitise_git_dir_name(".hidden", false).unwrap(),
+            "_hidden"
+        );
+        assert_eq!(
+            App::sanitise_git_dir_name("refs.lock", false).unwrap(),
+            "refs_lock"
+        );
+        assert_eq!(
+            App::sanitise_git_dir_name("feature/.x.lock", true).unwrap(),
+            "feature/_x_lock"
+        );
+    }
+
+    #[test]
+    fn drops_empty_components() {
+        assert_eq!(App::sanitise_git_dir_name("/a//b/", true).unwrap(), "a/b");
+        assert_eq!(App::sanitise_git_dir_name("/a//b/", false).unwrap(), "a_b");
+    }
+
+    #[test]
+    fn rejects_names_with_nothing_left() {
+        for name in ["", "/", "//", "///"] {
+            assert!(
+                App::sanitise_git_dir_name(name, true).is_err(),
+                "{:?}",
+                name
+            );
+            assert!(
+                App::sanitise_git_dir_name(name, false).is_err(),
+                "{:?}",
+                name
+            );
+        }
+        // dots git rejects are replaced rather than dropped, so something is always left.
+        for name in ["..", ".", ".lock", "/../"] {
+            assert!(!App::sanitise_git_dir_name(name, true).unwrap().is_empty());
+        }
+    }
+}
@@ -0,0 +1,188 @@
+use std::{
+    env,
+    ffi::OsString,
+    fs,
+    io::{self, Read, Write},
+    net::Shutdown,
+    os::{
+        fd::AsRawFd,
+        unix::net::{UnixListener, UnixStream},
+    },
+    path::{Path, PathBuf},
+    process::{self, ExitCode},
+    sync::{
+        OnceLock,
+        mpsc::{self, Receiver},
+    },
+    thread,
+    time::Duration,
+};
+
+use crate::config;
+
+/// set on git and ssh when gpm is their askpass program, so that gpm knows it was started to ask
+/// for a credential, and where to send the prompt.
+pub const SOCKET_VAR: &str = "GPM_ASKPASS_SOCKET";
+
+/// starts a reply that carries an answer. a reply without it means the prompt was cancelled.
+const ANSWERED: &str = "+";
+
+/// how long a prompt has to arrive once git or ssh has connected.
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// the socket prompts are sent to, once the TUI is listening for them.
+static SOCKET: OnceLock<PathBuf> = OnceLock::new();
+
+/// a prompt from git or ssh, waiting for the user to answer it.
+#[derive(Debug)]
+pub struct Request {
+    pub prompt: String,
+    stream: UnixStream,
+}
+
+impl Request {
+    /// whether the answer should be hidden as it is typed.
+    pub fn is_secret(&self) -> bool {
+        let prompt = self.prompt.to_lowercase();
+        ["password", "passphrase", "pin for"]
+            .iter()
+            .any(|s| prompt.contains(s))
+    }
+
+    pub fn answer(mut self, answer: &str) {
+        // the command may have been cancelled while waiting, in which case nobody is listening.
+        let _ = self
+            .stream
+            .write_all(format!("{}{}", ANSWERED, answer).as_bytes());
+    }
+
+    /// tells git or ssh there is no answer, so the command fails.
+    pub fn cancel(self) {}
+}
+
+/// listens for prompts from git and ssh run by background tasks.
+#[derive(Debug)]
+pub struct Server {
+    path: PathBuf,
+    requests: Receiver<Request>,
+}
+
+impl Server {
+    /// the next prompt waiting to be answered, if any.
+    pub fn poll(&self) -> Option<Request> {
+        self.requests.try_recv().ok()
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// starts listening for prompts. commands run on tasks afterwards use gpm as their askpass
+/// program. prompts are only taken from processes run by the same user.
+pub fn listen() -> io::Result<Server> {
+    let dir = config::runtime_dir()?;
+    let path = dir.join(format!("askpass-{}.sock", process::id()));
+    let _ = fs::remove_file(&path);
+    let listener = UnixListener::bind(&path)?;
+
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else {
+                continue;
+            };
+            // the answer may be a password, so it is never given to another user.
+            if peer_uid(&stream).ok() != Some(unsafe { libc::getuid() }) {
+                continue;
+            }
+            let _ = stream.set_read_timeout(Some(READ_TIMEOUT));
+            let mut prompt = String::new();
+            if stream.read_to_string(&mut prompt).is_err() {
+                continue;
+            }
+            let request = Request {
+                prompt: prompt.trim().to_string(),
+                stream,
+            };
+            if tx.send(request).is_err() {
+                break;
+            }
+        }
+    });
+
+    let _ = SOCKET.set(path.clone());
+    Ok(Server { path, requests: rx })
+}
+
+/// the user id of the process at the other end of `stream`.
+#[cfg(target_os = "linux")]
+fn peer_uid(stream: &UnixStream) -> io::Result<libc::uid_t> {
+    let mut cred: libc::ucred = unsafe { std::mem::zeroed() };
+    let mut len = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+    let ret = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_PEERCRED,
+            &mut cred as *mut libc::ucred as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    if ret != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(cred.uid)
+}
+
+/// the user id of the process at the other end of `stream`.
+#[cfg(not(target_os = "linux"))]
+fn peer_uid(stream: &UnixStream) -> io::Result<libc::uid_t> {
+    let mut uid: libc::uid_t = 0;
+    let mut gid: libc::gid_t = 0;
+    if unsafe { libc::getpeereid(stream.as_raw_fd(), &mut uid, &mut gid) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(uid)
+}
+
+/// the environment that makes git and ssh ask gpm for credentials and host key confirmations,
+/// or nothing if gpm is not listening for them.
+pub fn env() -> Vec<(&'static str, OsString)> {
+    let (Some(socket), Ok(exe)) = (SOCKET.get(), env::current_exe()) else {
+        return vec![];
+    };
+    vec![
+        ("GIT_ASKPASS", exe.clone().into_os_string()),
+        ("SSH_ASKPASS", exe.into_os_string()),
+        ("SSH_ASKPASS_REQUIRE", OsString::from("force")),
+        (SOCKET_VAR, socket.clone().into_os_string()),
+    ]
+}
+
+/// runs as git or ssh's askpass program: sends `prompt` to the TUI listening on `socket` and
+/// prints the answer.
+pub fn ask(socket: &Path, prompt: &str) -> ExitCode {
+    let answer = UnixStream::connect(socket).and_then(|mut stream| {
+        stream.write_all(prompt.as_bytes())?;
+        stream.shutdown(Shutdown::Write)?;
+        let mut reply = String::new();
+        stream.read_to_string(&mut reply)?;
+        Ok(reply)
+    });
+    match answer {
+        Ok(reply) => match reply.strip_prefix(ANSWERED) {
+            Some(answer) => {
+                println!("{}", answer);
+                ExitCode::SUCCESS
+            }
+            None => ExitCode::FAILURE,
+        },
+        Err(e) => {
+            eprintln!("gpm: could not ask for {:?}: {}", prompt, e);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,278 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use directories::ProjectDirs;
+
+use crate::{
+    exec::{self, CommandExt},
+    git_url::GitUrl,
+    ops::{RepoStep, summarise_output},
+    transaction::Transaction,
+};
+
+/// the config that stops git deleting objects from a mirror.
+const NO_GC: [&str; 2] = ["gc.auto=0", "gc.pruneExpire=never"];
+
+/// the directory holding the mirror clones that new clones borrow objects from.
+pub fn cache_dir() -> Option<PathBuf> {
+    ProjectDirs::from("", "", "gpm").map(|d| d.cache_dir().join("mirrors"))
+}
+
+/// where the mirror of `link` is kept, `<cache>/<host>/<path>.git`. links without a host are kept
+/// under `local/`.
+pub fn mirror_path(link: &str) -> Result<PathBuf, String> {
+    let url = GitUrl::parse(link)?;
+    let Some(mut path) = cache_dir() else {
+        return Err("Could not find a cache directory for gpm.".to_string());
+    };
+    path.push(url.host.as_deref().unwrap_or("local"));
+    for component in url.path.split('/').filter(|c| !c.is_empty()) {
+        if component == "." || component == ".." {
+            return Err(format!("'{}' is not a valid repository path.", url.path));
+        }
+        path.push(component);
+    }
+    // appended rather than set as the extension, so that e.g. `v1.2` and `v1.3` are kept apart.
+    let mut path = path.into_os_string();
+    path.push(".git");
+    Ok(PathBuf::from(path))
+}
+
+/// the command creating the mirror of `link` at `mirror`, or fetching into it if it already
+/// exists.
+///
+/// clones made with `--reference` read objects straight out of the mirror, so the mirror must
+/// never lose one: deleted branches are not pruned, and gc is turned off so that objects only
+/// reachable from clones are not thrown away.
+pub fn mirror_command(link: &str, mirror: &Path) -> Command {
+    let mut cmd = Command::new("git");
+    if mirror.is_dir() {
+        // mirrors made before gc was turned off in their config are covered here.
+        cmd.current_dir(mirror)
+            .args(NO_GC.iter().flat_map(|c| ["-c", c]))
+            .arg("fetch")
+            .args(exec::progress_flag())
+            .arg("origin");
+    } else {
+        cmd.arg("clone")
+            .arg("--mirror")
+            .args(NO_GC.iter().flat_map(|c| ["--config", c]))
+            .args(exec::progress_flag())
+            .arg(link)
+            .arg(mirror);
+    }
+    cmd
+}
+
+/// creates the mirror of `link`, or fetches into it if it already exists, returning its path and
+/// the summary lines.
+pub fn update_mirror(link: &str) -> Result<(PathBuf, Vec<String>), Vec<String>> {
+    let mirror = mirror_path(link).map_err(|e| vec![e])?;
+
+    if !mirror.is_dir()
+        && let Some(parent) = mirror.parent()
+        && let Err(e) = fs::create_dir_all(parent)
+    {
+        return Err(vec![
+            format!("Failed to create directory {:?}", parent),
+            format!("Error: {}", e),
+        ]);
+    }
+    let output = mirror_command(link, &mirror)
+        .run()
+        .map_err(|e| vec![format!("Failed to start 'git' process: {}", e)])?;
+
+    let success = output.status.success();
+    let summary = summarise_output(&format!("Updating clone cache {:?}", mirror), output);
+    if success {
+        Ok((mirror, summary))
+    } else {
+        Err(summary)
+    }
+}
+
+/// the alternates file of the repo at `repo`, if it borrows objects from another repo such as a
+/// clone cache mirror.
+pub fn alternates_file(repo: &Path) -> Option<PathBuf> {
+    let output = Command::new("git")
+        .current_dir(repo)
+        .arg("rev-parse")
+        .arg("--git-path")
+        .arg("objects/info/alternates")
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let path = repo.join(String::from_utf8_lossy(&output.stdout).trim());
+    path.is_file().then_some(path)
+}
+
+/// plans copying every object the repo at `repo` borrows into its own object store, then stopping
+/// it borrowing, so the mirror it was cloned with can be deleted.
+pub fn plan_dissociate(repo: &Path) -> Result<Transaction<RepoStep>, String> {
+    let Some(alternates) = alternates_file(repo) else {
+        return Err(format!(
+            "{:?} does not borrow objects from a clone cache.",
+            repo
+        ));
+    };
+    let previous = fs::read_to_string(&alternates).map_err(|e| e.to_string())?;
+
+    Ok(Transaction::new(
+        format!("Dissociate {:?} from the clone cache", repo),
+        repo.to_path_buf(),
+        dissociate_steps(repo, alternates, previous),
+    ))
+}
+
+/// plans deleting every mirror in the clone cache. the repos among `repos` that borrow objects
+/// from a mirror are dissociated from it first, so that they keep working. repos gpm does not
+/// know about that borrow from a mirror will break.
+pub fn plan_clear(repos: &[PathBuf]) -> Result<Transaction<RepoStep>, String> {
+    let Some(dir) = cache_dir() else {
+        return Err("Could not find a cache directory for gpm.".to_string());
+    };
+    plan_clear_dir(&dir, repos)
+}
+
+fn plan_clear_dir(dir: &Path, repos: &[PathBuf]) -> Result<Transaction<RepoStep>, String> {
+    if !dir.is_dir() {
+        return Err(format!("The clone cache {:?} is already empty.", dir));
+    }
+    let mut steps = vec![];
+    for repo in repos.iter() {
+        let Some(alternates) = alternates_file(repo) else {
+            continue;
+        };
+        let previous = fs::read_to_string(&alternates).map_err(|e| e.to_string())?;
+        if previous
+            .lines()
+            .any(|l| Path::new(l.trim()).starts_with(dir))
+        {
+            steps.extend(dissociate_steps(repo, alternates, previous));
+        }
+    }
+    steps.push(RepoStep::RemoveDir(dir.to_path_buf()));
+    Ok(Transaction::new(
+        "Clear the clone cache".to_string(),
+        dir.to_path_buf(),
+        steps,
+    ))
+}
+
+/// copies every borrowed object into the repo at `repo`, then removes its alternates file.
+fn dissociate_steps(repo: &Path, alternates: PathBuf, previous: String) -> Vec<RepoStep> {
+    vec![
+        RepoStep::git(repo, &["repack", "-a", "-d"]),
+        RepoStep::RemoveFile {
+            path: alternates,
+            previous,
+        },
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{TempDir, git};
+
+    /// a bare repo in `dir` with a `main` and a `topic` branch.
+    fn bare_remote(dir: &Path) -> PathBuf {
+        let work = dir.join("work");
+        let remote = dir.join("remote.git");
+        fs::create_dir(&work).unwrap();
+        git(&work, &["init", "-q", "-b", "main"]);
+        git(&work, &["commit", "-q", "--allow-empty", "-m", "main"]);
+        git(&work, &["checkout", "-q", "-b", "topic"]);
+        git(&work, &["commit", "-q", "--allow-empty", "-m", "topic"]);
+        git(dir, &["init", "-q", "--bare", "remote.git"]);
+        git(
+            &work,
+            &["push", "-q", remote.to_str().unwrap(), "main", "topic"],
+        );
+        remote
+    }
+
+    fn run(mut cmd: Command) {
+        let output = cmd.output().unwrap();
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    #[test]
+    fn mirror_turns_off_gc() {
+        let tmp = TempDir::new();
+        let remote = bare_remote(&tmp.0);
+        let mirror = tmp.0.join("mirror.git");
+        run(mirror_command(remote.to_str().unwrap(), &mirror));
+
+        assert_eq!(git(&mirror, &["config", "gc.auto"]), "0");
+        assert_eq!(git(&mirror, &["config", "gc.pruneExpire"]), "never");
+    }
+
+    #[test]
+    fn mirror_keeps_branches_deleted_from_the_remote() {
+        let tmp = TempDir::new();
+        let remote = bare_remote(&tmp.0);
+        let mirror = tmp.0.join("mirror.git");
+        let link = remote.to_str().unwrap();
+        run(mirror_command(link, &mirror));
+        let topic = git(&mirror, &["rev-parse", "refs/heads/topic"]);
+
+        git(&remote, &["branch", "-q", "-D", "topic"]);
+        run(mirror_command(link, &mirror));
+
+        assert_eq!(git(&mirror, &["rev-parse", "refs/heads/topic"]), topic);
+    }
+
+    #[test]
+    fn clearing_dissociates_repos_borrowing_from_the_cache() {
+        let tmp = TempDir::new();
+        let remote = bare_remote(&tmp.0);
+        let cache = tmp.0.join("cache");
+        let mirror = cache.join("local").join("remote.git");
+        run(mirror_command(remote.to_str().unwrap(), &mirror));
+        git(
+            &tmp.0,
+            &[
+                "clone",
+                "-q",
+                "--reference",
+                mirror.to_str().unwrap(),
+                remote.to_str().unwrap(),
+                "borrowing",
+            ],
+        );
+        git(&tmp.0, &["clone", "-q", remote.to_str().unwrap(), "plain"]);
+
+        let plan = plan_clear_dir(&cache, &[tmp.0.join("plain"), tmp.0.join("borrowing")]).unwrap();
+        let preview = plan.preview();
+        assert_eq!(preview.len(), 3, "{:?}", preview);
+        assert!(preview[0].contains("repack") && preview[0].contains("borrowing"));
+        assert!(preview[1].contains("alternates"));
+        assert_eq!(preview[2], format!("3. delete {:?}", cache));
+    }
+
+    #[test]
+    fn clearing_an_empty_cache_fails() {
+        let tmp = TempDir::new();
+        assert!(plan_clear_dir(&tmp.0.join("cache"), &[]).is_err());
+    }
+
+    #[test]
+    fn mirror_names_keep_dots() {
+        let mirror = mirror_path("https://example.com/owner/v1.2").unwrap();
+        assert!(
+            mirror.ends_with("example.com/owner/v1.2.git"),
+            "{:?}",
+            mirror
+        );
+    }
+}
@@ -0,0 +1,163 @@
+use std::{path::Path, process::ExitCode};
+
+use crate::{
+    cache,
+    config::Config,
+    git_url::GitUrl,
+    history, ops, project_item,
+    rewrite::{self, UrlRewrite},
+    transaction::{Step, Transaction},
+};
+
+const USAGE: &str = "usage: gpm [--dry-run] [get <repo link> | dissociate <repo path> | clear-cache | log [<filter>] | rewrite-remotes <mode> <match> [<replacement>] [--apply]]";
+
+/// runs a gpm subcommand without starting the tui. with `dry_run` set, the commands it would run
+/// are printed instead.
+pub fn run(config: &Config, args: &[String], dry_run: bool) -> ExitCode {
+    match args {
+        [cmd, link] if cmd == "get" => get(config, link, dry_run),
+        [cmd, path] if cmd == "dissociate" => {
+            run_plan(cache::plan_dissociate(Path::new(path)), dry_run)
+        }
+        [cmd] if cmd == "clear-cache" => {
+            let repos = project_item::forest_repos(&config.to_forest());
+            run_plan(cache::plan_clear(&repos), dry_run)
+        }
+        [cmd] if cmd == "log" => log(""),
+        [cmd, filter] if cmd == "log" => log(filter),
+        [cmd, rest @ ..] if cmd == "rewrite-remotes" => rewrite_remotes(config, rest, dry_run),
+        _ => {
+            eprintln!("{}", USAGE);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// clones `link` as a worktree repo into `<root>/<host>/<owner>/<repo>`, where the root is the
+/// first project directory with host-owner-repo placement.
+fn get(config: &Config, link: &str, dry_run: bool) -> ExitCode {
+    let Some(root) = config.placement_root() else {
+        eprintln!("no project directory has `placement = \"host-owner-repo\"` set in its options.");
+        return ExitCode::FAILURE;
+    };
+
+    let link = config.expand_clone_link(link);
+    let target = GitUrl::parse(&link).and_then(|url| {
+        let parent = config.clone_parent(&root, &link)?;
+        Ok((parent, url.repo_name().to_string()))
+    });
+    let (parent, name) = match target {
+        Ok(target) => target,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let options = config.options_for(&root);
+    let layout = options.layout;
+    if layout.repo_path(&parent, &name).exists() {
+        println!("{:?} already exists.", layout.repo_path(&parent, &name));
+        return ExitCode::SUCCESS;
+    }
+
+    run_plan(
+        ops::plan_worktree_clone(layout, &parent, &name, &link, &options.clone_options),
+        dry_run,
+    )
+}
+
+/// prints every operation in the history matching `filter`, oldest first, with the outcome of
+/// each of its steps and the commands it ran. the output of commands that failed is included.
+fn log(filter: &str) -> ExitCode {
+    for entry in history::load().iter().filter(|e| e.matches(filter)) {
+        println!("{}", entry.headline());
+        for (i, step) in entry.steps.iter().enumerate() {
+            println!("    {}. {}: {}", i + 1, step.outcome, step.description);
+        }
+        for c in entry.commands.iter() {
+            match c.exit_code {
+                Some(0) => println!("    $ {}", c.command_line()),
+                Some(code) => println!("    $ {}  [exit {}]", c.command_line(), code),
+                None => println!("    $ {}  [killed]", c.command_line()),
+            }
+            if c.exit_code != Some(0) {
+                c.stderr.lines().for_each(|l| println!("        {}", l));
+            }
+        }
+    }
+    ExitCode::SUCCESS
+}
+
+/// previews rewriting the remote urls of every repo gpm knows about, and applies the rewrite if
+/// `--apply` is given.
+fn rewrite_remotes(config: &Config, args: &[String], dry_run: bool) -> ExitCode {
+    let apply = args.iter().any(|a| a == "--apply");
+    let args: Vec<&str> = args
+        .iter()
+        .map(|a| a.as_str())
+        .filter(|a| *a != "--apply")
+        .collect();
+    let rewrite = match args[..] {
+        [mode, pattern] => UrlRewrite::new(mode, pattern, ""),
+        [mode, pattern, replacement] => UrlRewrite::new(mode, pattern, replacement),
+        _ => Err(format!("{}\nmodes: {}", USAGE, rewrite::MODES)),
+    };
+    let rewrite = match rewrite {
+        Ok(rewrite) => rewrite,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let changes = rewrite::plan(&project_item::forest_repos(&config.to_forest()), &rewrite);
+    if !apply {
+        for c in changes.iter() {
+            println!(
+                "{:?} {}{}: {} -> {}",
+                c.repo,
+                c.remote,
+                if c.push { " (push)" } else { "" },
+                c.old_url,
+                c.new_url
+            );
+        }
+        println!(
+            "{} remote urls would change. run with --apply to rewrite them.",
+            changes.len()
+        );
+        return ExitCode::SUCCESS;
+    }
+
+    run_plan(Ok(rewrite::to_transaction(&changes)), dry_run)
+}
+
+/// runs `plan`, or prints the commands it would run if `dry_run` is set.
+fn run_plan<S: Step>(plan: Result<Transaction<S>, String>, dry_run: bool) -> ExitCode {
+    match plan {
+        Ok(transaction) if dry_run => {
+            transaction.dry_run().iter().for_each(|l| println!("{}", l));
+            ExitCode::SUCCESS
+        }
+        Ok(transaction) => report(transaction.run()),
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// prints the summary lines of an operation and turns its result into an exit code.
+fn report(result: Result<Vec<String>, Vec<String>>) -> ExitCode {
+    match result {
+        Ok(summary) => {
+            summary.iter().for_each(|l| println!("{}", l));
+            ExitCode::SUCCESS
+        }
+        Err(summary) => {
+            summary.iter().for_each(|l| eprintln!("{}", l));
+            ExitCode::FAILURE
+        }
+    }
+}
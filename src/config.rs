@@ -1,15 +1,95 @@
-use std::{fs::DirEntry, path::PathBuf};
+use std::{
+    collections::{HashMap, HashSet},
+    env,
+    fs::{self, DirBuilder, DirEntry, ReadDir},
+    io,
+    os::unix::fs::{DirBuilderExt, MetadataExt},
+    path::{Path, PathBuf},
+    process::Command,
+};
 
-use directories::UserDirs;
+use directories::{ProjectDirs, UserDirs};
+use ratatui::{
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+};
 use serde::{Deserialize, Serialize};
 use tui_tree_widget::TreeItem;
 
-use crate::project_item::{ProjectItem, ProjectItemType};
+use crate::{
+    git,
+    git_url::{self, GitUrl},
+    layout::RepoLayout,
+    ops::CloneOptions,
+    project_item::{ProjectItem, ProjectItemType},
+};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Config {
     project_directories: Vec<String>,
     standalone_projects: Vec<String>,
+    /// per project directory settings, keyed by the same string used in `project_directories`.
+    #[serde(default)]
+    project_directory_options: HashMap<String, ProjectDirectoryOptions>,
+    /// shorthand prefixes for clone links, e.g. `gh = "https://github.com/{path}.git"` lets
+    /// `gh:owner/repo` be typed instead of the full link.
+    #[serde(default = "default_clone_shorthands")]
+    clone_shorthands: HashMap<String, String>,
+    /// initialise submodules in new clones and worktrees. can be overridden per project directory.
+    #[serde(default)]
+    init_submodules: bool,
+    /// how many background jobs may run at once. the rest wait in the queue.
+    #[serde(default = "default_max_concurrent_jobs")]
+    max_concurrent_jobs: usize,
+    /// the command `s` runs in the selected project instead of `$SHELL`, e.g. `lazygit`. it is
+    /// run with `sh -c`.
+    #[serde(default)]
+    shell_command: Option<String>,
+}
+
+fn default_max_concurrent_jobs() -> usize {
+    2
+}
+
+fn default_clone_shorthands() -> HashMap<String, String> {
+    HashMap::from([(
+        "gh".to_string(),
+        "https://github.com/{path}.git".to_string(),
+    )])
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct ProjectDirectoryOptions {
+    /// the on-disk layout used when cloning worktree repos into this directory.
+    pub layout: RepoLayout,
+    /// a subdirectory of the worktree root that new worktrees are created in, e.g. `worktrees`.
+    pub worktree_subdir: Option<String>,
+    /// create nested directories for slash separated branch names (`feature/login/`) instead of
+    /// flattening them (`feature_login`).
+    pub nested_worktree_dirs: bool,
+    /// template for new branch names, e.g. `{user}/{ticket}-{slug}`. supports `{user}`, `{date}`,
+    /// `{ticket}` and `{slug}`.
+    pub branch_template: Option<String>,
+    /// template for new worktree directory names, with the same variables as `branch_template`.
+    pub worktree_dir_template: Option<String>,
+    /// where cloned repos are put inside the project directory.
+    pub placement: Placement,
+    /// defaults for the clone and new worktree forms.
+    pub clone_options: CloneOptions,
+    /// overrides the global `init_submodules` setting for this directory.
+    pub init_submodules: Option<bool>,
+}
+
+/// how cloned repos are arranged inside a project directory.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Placement {
+    /// directly inside the project directory.
+    #[default]
+    Flat,
+    /// under `<host>/<owner>/<repo>`, as ghq does.
+    HostOwnerRepo,
 }
 
 impl Default for Config {
@@ -17,138 +97,638 @@ impl Default for Config {
         Self {
             project_directories: vec!["~/proj".to_string()],
             standalone_projects: vec!["~/.dotfiles".to_string()],
+            project_directory_options: HashMap::new(),
+            clone_shorthands: default_clone_shorthands(),
+            init_submodules: false,
+            max_concurrent_jobs: default_max_concurrent_jobs(),
+            shell_command: None,
         }
     }
 }
 
 impl Config {
-    pub fn to_forest(&self) -> Vec<TreeItem<ProjectItem>> {
-        let mut forest = vec![];
-
-        let home_dir = UserDirs::new().unwrap().home_dir().to_path_buf();
-
-        for proj in self.standalone_projects.iter() {
-            let path: PathBuf = if proj.starts_with("~/") {
-                home_dir.join(PathBuf::from(proj.strip_prefix("~/").unwrap()))
-            } else {
-                home_dir.join(PathBuf::from(proj))
-            };
-            let tree_item = ProjectItem::new(path.clone(), ProjectItemType::NonWorktreeRepo);
-            let name: String = path.file_name().unwrap().to_str().unwrap().to_owned();
-            forest.push(TreeItem::new_leaf(tree_item, name));
+    /// expands any configured shorthand at the start of a clone link.
+    pub fn expand_clone_link(&self, link: &str) -> String {
+        git_url::expand_shorthand(link, &self.clone_shorthands)
+    }
+
+    /// the options of the project directory containing `path`, or the defaults if `path` is not
+    /// inside any project directory.
+    pub fn options_for(&self, path: &Path) -> ProjectDirectoryOptions {
+        self.project_directories
+            .iter()
+            .find(|p| path.starts_with(expand_path(p)))
+            .and_then(|p| self.project_directory_options.get(p))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// whether submodules should be initialised in new clones and worktrees at `path`.
+    pub fn init_submodules_for(&self, path: &Path) -> bool {
+        self.options_for(path)
+            .init_submodules
+            .unwrap_or(self.init_submodules)
+    }
+
+    /// how many background jobs may run at once, at least one.
+    pub fn max_concurrent_jobs(&self) -> usize {
+        self.max_concurrent_jobs.max(1)
+    }
+
+    /// the configured shell command, or the user's shell.
+    pub fn shell_command(&self) -> Command {
+        match self.shell_command {
+            Some(ref command) => {
+                let mut cmd = Command::new("sh");
+                cmd.arg("-c").arg(command);
+                cmd
+            }
+            None => Command::new(env::var_os("SHELL").unwrap_or_else(|| "/bin/sh".into())),
         }
+    }
 
-        for project_dir in self.project_directories.iter() {
-            let path: PathBuf = if project_dir.starts_with("~/") {
-                home_dir.join(PathBuf::from(project_dir.strip_prefix("~/").unwrap()))
-            } else {
-                home_dir.join(PathBuf::from(project_dir))
-            };
-            let Ok(contents) = path.read_dir() else {
-                eprintln!(
-                    "{} was set as a project directory but is not a directory. skipping.",
-                    path.to_string_lossy()
-                );
-                continue;
-            };
+    /// the first project directory that places repos by host and owner, used by `gpm get`.
+    pub fn placement_root(&self) -> Option<PathBuf> {
+        self.project_directories
+            .iter()
+            .find(|p| {
+                self.project_directory_options
+                    .get(*p)
+                    .is_some_and(|o| o.placement == Placement::HostOwnerRepo)
+            })
+            .map(|p| expand_path(p))
+    }
 
-            let ct: Vec<DirEntry> = contents.into_iter().filter_map(|d| d.ok()).collect();
+    /// the directory a repo cloned from `link` into `project_dir` should be created in. for
+    /// host-owner-repo placement this is `project_dir/<host>/<owner>`.
+    pub fn clone_parent(&self, project_dir: &Path, link: &str) -> Result<PathBuf, String> {
+        match self.options_for(project_dir).placement {
+            Placement::Flat => Ok(project_dir.to_path_buf()),
+            Placement::HostOwnerRepo => {
+                let url = GitUrl::parse(link)?;
+                Ok(project_dir.join(url.placement_dir()?))
+            }
+        }
+    }
 
-            let mut children: Vec<TreeItem<ProjectItem>> = vec![];
+    pub fn to_forest(&self) -> Vec<TreeItem<'static, ProjectItem>> {
+        self.roots()
+            .iter()
+            .filter_map(|root| self.root_item(root))
+            .collect()
+    }
 
-            for subdir in ct.iter() {
-                if !subdir.path().is_dir() {
-                    continue;
+    /// rebuilds only the items of `forest` that `changed` affects. a repo with a change inside it
+    /// is rebuilt on its own, and the directories holding a directory that appeared or
+    /// disappeared are re-listed, reusing every item in them that did not change.
+    pub fn refresh_forest(
+        &self,
+        forest: &[TreeItem<'static, ProjectItem>],
+        changed: &[PathBuf],
+    ) -> Vec<TreeItem<'static, ProjectItem>> {
+        self.roots()
+            .iter()
+            .filter_map(|root| {
+                let path = root.path();
+                let Some(item) = forest.iter().find(|item| item.identifier().path == *path) else {
+                    return self.root_item(root);
+                };
+                if !is_affected(item, changed) {
+                    return Some(item.clone());
                 }
+                match root {
+                    Root::ProjectDirectory(project_dir, path) if path.is_dir() => {
+                        let mut unchanged = HashMap::new();
+                        unchanged_items(item.children(), changed, &mut unchanged);
+                        self.project_directory_item(project_dir, path, &unchanged)
+                    }
+                    _ => self.root_item(root),
+                }
+            })
+            .collect()
+    }
+
+    fn roots(&self) -> Vec<Root<'_>> {
+        let standalone = self
+            .standalone_projects
+            .iter()
+            .map(|p| Root::Standalone(expand_path(p)));
+        let project_dirs = self
+            .project_directories
+            .iter()
+            .map(|p| Root::ProjectDirectory(p, expand_path(p)));
+        standalone.chain(project_dirs).collect()
+    }
 
-                let Ok(subdir_contents) = subdir.path().read_dir() else {
+    fn root_item(&self, root: &Root) -> Option<TreeItem<'static, ProjectItem>> {
+        match root {
+            Root::Standalone(path) => {
+                if !path.is_dir() {
                     eprintln!(
-                        "{} was found as a subdir but is not a directory. skipping.",
+                        "{} was set as a standalone project but is not a directory. skipping.",
                         path.to_string_lossy()
                     );
-                    continue;
-                };
-
-                let subdir_ct: Vec<DirEntry> =
-                    subdir_contents.into_iter().filter_map(|d| d.ok()).collect();
-
-                if !subdir_ct.iter().any(|d| d.file_name() == ".bare") {
-                    // this is a project, not a project home.
-                    let name = subdir
-                        .path()
-                        .file_name()
-                        .unwrap()
-                        .to_string_lossy()
-                        .into_owned();
-
-                    children.push(TreeItem::new_leaf(
-                        ProjectItem::new(subdir.path(), ProjectItemType::NonWorktreeRepo),
-                        name,
-                    ));
-                    continue;
+                    return None;
                 }
+                let options = self.options_for(path);
+                Some(project_tree_item(path.clone(), &options))
+            }
+            Root::ProjectDirectory(project_dir, path) => {
+                self.project_directory_item(project_dir, path, &HashMap::new())
+            }
+        }
+    }
 
-                let mut sub_children: Vec<TreeItem<ProjectItem>> = vec![];
-
-                for proj in subdir_ct.iter() {
-                    let proj_path = proj.path();
-                    let name = proj
-                        .path()
-                        .file_name()
-                        .unwrap()
-                        .to_string_lossy()
-                        .into_owned();
-                    if name == ".bare" || name == ".git" {
-                        continue;
-                    }
-                    sub_children.push(TreeItem::new_leaf(
-                        ProjectItem::new(proj_path, ProjectItemType::Worktree),
-                        name,
-                    ));
-                }
-                sub_children.sort_by_key(|c| {
-                    c.identifier()
-                        .path
-                        .file_name()
-                        .unwrap()
-                        .to_string_lossy()
-                        .into_owned()
-                });
-
-                let display_name = if sub_children.is_empty() {
-                    format!("▶ {}", subdir.path().file_name().unwrap().to_string_lossy())
-                } else {
-                    subdir.path().file_name().unwrap().to_string_lossy().to_string()
-                };
+    /// builds the item of the project directory configured as `project_dir`, reusing the items in
+    /// `unchanged` rather than reading their directories again.
+    fn project_directory_item(
+        &self,
+        project_dir: &str,
+        path: &Path,
+        unchanged: &HashMap<PathBuf, TreeItem<'static, ProjectItem>>,
+    ) -> Option<TreeItem<'static, ProjectItem>> {
+        let Ok(contents) = path.read_dir() else {
+            eprintln!(
+                "{} was set as a project directory but is not a directory. skipping.",
+                path.to_string_lossy()
+            );
+            return None;
+        };
+
+        let options = self
+            .project_directory_options
+            .get(project_dir)
+            .cloned()
+            .unwrap_or_default();
 
+        let children = directory_tree_items(contents, &options, unchanged);
+
+        Some(
+            TreeItem::new(
+                ProjectItem::new(path.to_path_buf(), ProjectItemType::ProjectDirectory),
+                format!("{}", path.file_name().unwrap().to_string_lossy()),
+                children,
+            )
+            .unwrap(),
+        )
+    }
+}
+
+/// a top level item of the tree.
+enum Root<'a> {
+    Standalone(PathBuf),
+    /// a project directory, with the string it is configured as.
+    ProjectDirectory(&'a str, PathBuf),
+}
+
+impl Root<'_> {
+    fn path(&self) -> &PathBuf {
+        match self {
+            Self::Standalone(path) | Self::ProjectDirectory(_, path) => path,
+        }
+    }
+}
+
+/// whether any of `changed` is the directory of `item` or inside it.
+fn is_affected(item: &TreeItem<ProjectItem>, changed: &[PathBuf]) -> bool {
+    changed
+        .iter()
+        .any(|c| c.starts_with(&item.identifier().path))
+}
+
+/// collects the items among `items` that `changed` does not affect, by path, looking inside the
+/// groups of repos that it does.
+fn unchanged_items(
+    items: &[TreeItem<'static, ProjectItem>],
+    changed: &[PathBuf],
+    unchanged: &mut HashMap<PathBuf, TreeItem<'static, ProjectItem>>,
+) {
+    for item in items {
+        let id = item.identifier();
+        if !is_affected(item, changed) {
+            unchanged.insert(id.path.clone(), item.clone());
+        } else if id.project_type == ProjectItemType::Group {
+            unchanged_items(item.children(), changed, unchanged);
+        }
+    }
+}
+
+/// builds the tree items for the directories in `contents`, with the worktrees of sibling layout
+/// repos and linked worktrees that are already nested under their repo left out. with host-owner-repo
+/// placement, directories that are not repos become groups of the repos inside them. the items in
+/// `unchanged` are reused rather than built again.
+fn directory_tree_items(
+    contents: ReadDir,
+    options: &ProjectDirectoryOptions,
+    unchanged: &HashMap<PathBuf, TreeItem<'static, ProjectItem>>,
+) -> Vec<TreeItem<'static, ProjectItem>> {
+    let ct: Vec<DirEntry> = contents.into_iter().filter_map(|d| d.ok()).collect();
+
+    let mut children: Vec<TreeItem<ProjectItem>> = vec![];
+
+    for subdir in ct.iter() {
+        let path = subdir.path();
+        if !path.is_dir() {
+            continue;
+        }
+        if let Some(item) = unchanged.get(&path) {
+            children.push(item.clone());
+            continue;
+        }
+
+        if options.placement == Placement::HostOwnerRepo
+            && !git::is_git_repo(&path)
+            && let Ok(contents) = path.read_dir()
+        {
+            // a group is only made if there is a repo somewhere inside it.
+            let group_children = directory_tree_items(contents, options, unchanged);
+            if group_children
+                .iter()
+                .any(|c| c.identifier().project_type != ProjectItemType::PlainDirectory)
+            {
+                let name = path.file_name().unwrap().to_string_lossy().into_owned();
                 children.push(
                     TreeItem::new(
-                        ProjectItem::new(subdir.path(), ProjectItemType::WorktreeRepo),
-                        display_name,
-                        sub_children,
+                        ProjectItem::new(path, ProjectItemType::Group),
+                        format!("{}/", name),
+                        group_children,
                     )
                     .unwrap(),
                 );
+                continue;
+            }
+        }
+
+        children.push(project_tree_item(path, options));
+    }
+
+    // the worktree directories of sibling layout repos are shown under the `.git` dir.
+    let sibling_roots: HashSet<PathBuf> = children
+        .iter()
+        .map(|c| &c.identifier().path)
+        .filter(|p| RepoLayout::detect(p) == Some(RepoLayout::Sibling))
+        .map(|p| p.with_extension(""))
+        .collect();
+    children.retain(|c| {
+        c.identifier().project_type != ProjectItemType::PlainDirectory
+            || !sibling_roots.contains(&c.identifier().path)
+    });
+
+    // linked worktrees that live inside the project directory are already nested under the repo
+    // that owns them.
+    let worktree_paths = nested_worktree_paths(&children);
+    children.retain(|c| {
+        let p = &c.identifier().path;
+        !worktree_paths.contains(&p.canonicalize().unwrap_or_else(|_| p.clone()))
+    });
+
+    children.sort_by_key(|c| {
+        c.identifier()
+            .path
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .into_owned()
+    });
+    children
+}
+
+/// the directory gpm keeps its history and other state in between runs. platforms without a
+/// separate state directory use the local data directory.
+pub fn state_dir() -> Option<PathBuf> {
+    let dirs = ProjectDirs::from("", "", "gpm")?;
+    Some(
+        dirs.state_dir()
+            .unwrap_or_else(|| dirs.data_local_dir())
+            .to_path_buf(),
+    )
+}
+
+/// the directory gpm keeps files in that only last while it runs, such as sockets, which is
+/// created if it does not exist. platforms without a runtime directory use the temporary
+/// directory, where another user could have created it first, so it is only used if it is private
+/// to this user.
+pub fn runtime_dir() -> io::Result<PathBuf> {
+    let dir = ProjectDirs::from("", "", "gpm")
+        .and_then(|dirs| dirs.runtime_dir().map(Path::to_path_buf))
+        .unwrap_or_else(|| {
+            let uid = unsafe { libc::getuid() };
+            env::temp_dir().join(format!("gpm-{}", uid))
+        });
+    DirBuilder::new().recursive(true).mode(0o700).create(&dir)?;
+    check_private(&dir)?;
+    Ok(dir)
+}
+
+/// fails unless `dir` is a directory, not a symlink to one, which belongs to this user and which
+/// nobody else can read or write.
+fn check_private(dir: &Path) -> io::Result<()> {
+    let metadata = fs::symlink_metadata(dir)?;
+    let uid = unsafe { libc::getuid() };
+    let problem = if metadata.file_type().is_symlink() {
+        "is a symlink".to_string()
+    } else if !metadata.is_dir() {
+        "is not a directory".to_string()
+    } else if metadata.uid() != uid {
+        format!("belongs to user {}", metadata.uid())
+    } else if metadata.mode() & 0o777 != 0o700 {
+        format!("has mode {:o} rather than 700", metadata.mode() & 0o777)
+    } else {
+        return Ok(());
+    };
+    Err(io::Error::other(format!(
+        "{:?} {}, so it is not safe to use",
+        dir, problem
+    )))
+}
+
+/// resolves a path from the config, relative to the home directory.
+fn expand_path(p: &str) -> PathBuf {
+    let home_dir = UserDirs::new().unwrap().home_dir().to_path_buf();
+    if let Some(stripped) = p.strip_prefix("~/") {
+        home_dir.join(PathBuf::from(stripped))
+    } else {
+        home_dir.join(PathBuf::from(p))
+    }
+}
+
+/// classifies the directory at `path` and builds its tree item. the worktrees of a repo are
+/// nested underneath it as children, wherever they live on disk.
+fn project_tree_item(
+    path: PathBuf,
+    options: &ProjectDirectoryOptions,
+) -> TreeItem<'static, ProjectItem> {
+    let name = path.file_name().unwrap().to_string_lossy().into_owned();
+
+    if !git::is_git_repo(&path) {
+        return TreeItem::new_leaf(
+            ProjectItem::new(path, ProjectItemType::PlainDirectory),
+            Line::styled(
+                name,
+                Style::new()
+                    .fg(Color::DarkGray)
+                    .add_modifier(Modifier::ITALIC),
+            ),
+        );
+    }
+
+    let layout = RepoLayout::detect(&path);
+    let (project_type, name, worktree_root) = match layout {
+        Some(layout) => (
+            ProjectItemType::WorktreeRepo,
+            layout.display_name(&path),
+            layout.worktree_root(&path, None),
+        ),
+        None => (ProjectItemType::NonWorktreeRepo, name, path.clone()),
+    };
+
+    let canonical_roots: Vec<PathBuf> = [
+        worktree_root.join(options.worktree_subdir.as_deref().unwrap_or_default()),
+        worktree_root,
+    ]
+    .into_iter()
+    .map(|r| r.canonicalize().unwrap_or(r))
+    .collect();
+    let git_dir = match layout {
+        Some(layout) => layout.bare_dir(&path),
+        None => path.clone(),
+    };
+    // a `.git` file outside a worktree repo means this is itself a linked worktree, whose
+    // siblings belong to the repo that owns it.
+    let is_linked_worktree = layout.is_none() && path.join(".git").is_file();
+    let worktree_paths: Vec<PathBuf> = match git::linked_worktrees(&git_dir) {
+        _ if is_linked_worktree => vec![],
+        Some(worktrees) => worktrees.into_iter().map(|w| w.path).collect(),
+        None if project_type == ProjectItemType::WorktreeRepo => scan_worktree_dirs(
+            &layout
+                .unwrap()
+                .worktree_root(&path, options.worktree_subdir.as_deref()),
+        ),
+        None => vec![],
+    };
+
+    // worktrees inside the worktree root are keyed by their path relative to it, so that nested
+    // directories can be shown as groups. anything else is shown by name at the top level.
+    let mut nested: Vec<(PathBuf, Vec<Vec<String>>)> = vec![];
+    let mut sub_children: Vec<TreeItem<ProjectItem>> = vec![];
+    for wt_path in worktree_paths.into_iter() {
+        match canonical_roots
+            .iter()
+            .find_map(|root| wt_path.strip_prefix(root).ok().map(|rel| (root, rel)))
+            .filter(|(_, rel)| rel.components().next().is_some())
+        {
+            Some((root, rel)) => {
+                let components: Vec<String> = rel
+                    .components()
+                    .map(|c| c.as_os_str().to_string_lossy().into_owned())
+                    .collect();
+                match nested.iter_mut().find(|(r, _)| r == root) {
+                    Some((_, rels)) => rels.push(components),
+                    None => nested.push((root.clone(), vec![components])),
+                }
+            }
+            None => {
+                let wt_name = wt_path.file_name().unwrap().to_string_lossy().into_owned();
+                let label = checkout_label(&wt_path, wt_name);
+                sub_children.push(TreeItem::new_leaf(
+                    ProjectItem::new(wt_path, ProjectItemType::Worktree),
+                    label,
+                ));
             }
+        }
+    }
+    for (root, rels) in nested.into_iter() {
+        sub_children.extend(worktree_group_items(&root, rels));
+    }
 
-            children.sort_by_key(|c| {
-                c.identifier()
-                    .path
-                    .file_name()
-                    .unwrap()
-                    .to_string_lossy()
-                    .into_owned()
-            });
-
-            let project_dir_tree_item = TreeItem::new(
-                ProjectItem::new(path.clone(), ProjectItemType::ProjectDirectory),
-                format!("{}", path.file_name().unwrap().to_string_lossy()),
-                children,
+    let display_name = match project_type {
+        ProjectItemType::NonWorktreeRepo => checkout_label(&path, name),
+        _ if sub_children.is_empty() => Line::raw(format!("▶ {}", name)),
+        _ => Line::raw(name),
+    };
+
+    if project_type == ProjectItemType::NonWorktreeRepo && sub_children.is_empty() {
+        return TreeItem::new_leaf(ProjectItem::new(path, project_type), display_name);
+    }
+
+    TreeItem::new(
+        ProjectItem::new(path, project_type),
+        display_name,
+        sub_children,
+    )
+    .unwrap()
+}
+
+/// the label of a checked out repo or worktree, with a badge if its submodules need updating.
+fn checkout_label(path: &Path, name: String) -> Line<'static> {
+    match git::submodule_status(path).and_then(|s| s.badge()) {
+        Some(badge) => Line::from(vec![
+            Span::raw(name),
+            Span::raw(" "),
+            Span::styled(badge, Style::new().fg(Color::Yellow)),
+        ]),
+        None => Line::raw(name),
+    }
+}
+
+/// builds the tree items for worktrees under `root`, given as their path components relative to
+/// it. intermediate directories become collapsible groups.
+fn worktree_group_items(
+    root: &Path,
+    mut rels: Vec<Vec<String>>,
+) -> Vec<TreeItem<'static, ProjectItem>> {
+    rels.sort();
+
+    let mut items = vec![];
+    let mut i = 0;
+    while i < rels.len() {
+        let first = rels[i][0].clone();
+        let path = root.join(&first);
+
+        if rels[i].len() == 1 {
+            let label = checkout_label(&path, first);
+            items.push(TreeItem::new_leaf(
+                ProjectItem::new(path, ProjectItemType::Worktree),
+                label,
+            ));
+            i += 1;
+            continue;
+        }
+
+        let mut group_rels = vec![];
+        while i < rels.len() && rels[i][0] == first && rels[i].len() > 1 {
+            group_rels.push(rels[i][1..].to_vec());
+            i += 1;
+        }
+        let group_children = worktree_group_items(&path, group_rels);
+        items.push(
+            TreeItem::new(
+                ProjectItem::new(path, ProjectItemType::Group),
+                format!("{}/", first),
+                group_children,
             )
-            .unwrap();
+            .unwrap(),
+        );
+    }
+    items
+}
 
-            forest.push(project_dir_tree_item);
+/// fallback for when git cannot list the worktrees of a worktree repo; finds every directory in
+/// the worktree root with a `.git` file, at any depth.
+fn scan_worktree_dirs(path: &Path) -> Vec<PathBuf> {
+    let Ok(contents) = path.read_dir() else {
+        eprintln!(
+            "{} was found as a worktree repo but could not be read.",
+            path.to_string_lossy()
+        );
+        return vec![];
+    };
+
+    let mut worktrees = vec![];
+    for dir in contents.filter_map(|d| d.ok()).map(|d| d.path()) {
+        let name = dir.file_name().unwrap();
+        if !dir.is_dir() || name == ".bare" || name == ".git" {
+            continue;
+        }
+        if dir.join(".git").is_file() {
+            worktrees.push(dir);
+        } else {
+            worktrees.extend(scan_worktree_dirs(&dir));
         }
-        return forest;
+    }
+    worktrees
+}
+
+/// collects the canonical paths of every worktree nested under the items in `items`.
+fn nested_worktree_paths(items: &[TreeItem<ProjectItem>]) -> HashSet<PathBuf> {
+    let mut paths = HashSet::new();
+    for item in items.iter() {
+        for child in item.children() {
+            if child.identifier().project_type == ProjectItemType::Worktree {
+                let p = &child.identifier().path;
+                paths.insert(p.canonicalize().unwrap_or_else(|_| p.clone()));
+            }
+        }
+    }
+    paths
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::{PermissionsExt, symlink};
+
+    use super::*;
+    use crate::testing::{TempDir, git};
+
+    #[test]
+    fn only_private_directories_are_used() {
+        let tmp = TempDir::new();
+        let dir = tmp.0.join("runtime");
+        DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(&dir)
+            .unwrap();
+        assert!(check_private(&dir).is_ok());
+
+        let link = tmp.0.join("link");
+        symlink(&dir, &link).unwrap();
+        assert!(check_private(&link).is_err());
+
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o755)).unwrap();
+        assert!(check_private(&dir).is_err());
+
+        let file = tmp.0.join("file");
+        fs::write(&file, "").unwrap();
+        assert!(check_private(&file).is_err());
+    }
+
+    fn types(forest: &[TreeItem<ProjectItem>]) -> Vec<(String, ProjectItemType)> {
+        forest[0]
+            .children()
+            .iter()
+            .map(|c| {
+                let id = c.identifier();
+                let name = id.path.file_name().unwrap().to_string_lossy().into_owned();
+                (name, id.project_type)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn refresh_only_rebuilds_what_changed() {
+        let tmp = TempDir::new();
+        let dir = &tmp.0;
+        fs::create_dir(dir.join("a")).unwrap();
+        fs::create_dir(dir.join("b")).unwrap();
+        let config = Config {
+            project_directories: vec![dir.to_string_lossy().into_owned()],
+            standalone_projects: vec![],
+            ..Default::default()
+        };
+        let forest = config.to_forest();
+
+        // a becomes a repo without being reported, so its item is reused as it was.
+        git(&dir.join("a"), &["init", "-q"]);
+        fs::create_dir(dir.join("c")).unwrap();
+        let forest = config.refresh_forest(&forest, &[dir.join("c")]);
+        assert_eq!(
+            types(&forest),
+            [
+                ("a".to_string(), ProjectItemType::PlainDirectory),
+                ("b".to_string(), ProjectItemType::PlainDirectory),
+                ("c".to_string(), ProjectItemType::PlainDirectory),
+            ]
+        );
+
+        fs::remove_dir(dir.join("b")).unwrap();
+        let forest = config.refresh_forest(&forest, &[dir.join("a/.git"), dir.join("b")]);
+        assert_eq!(
+            types(&forest),
+            [
+                ("a".to_string(), ProjectItemType::NonWorktreeRepo),
+                ("c".to_string(), ProjectItemType::PlainDirectory),
+            ]
+        );
     }
 }
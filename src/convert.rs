@@ -0,0 +1,716 @@
+use std::{
+    ffi::OsString,
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use crate::{
+    exec::{self, CommandExt, display_command},
+    git,
+    layout::RepoLayout,
+    transaction::{Step, Transaction},
+};
+
+const FETCH_REFSPEC: &str = "+refs/heads/*:refs/remotes/origin/*";
+
+/// a single reversible change made while converting a repo between layouts.
+#[derive(Debug, Clone)]
+pub enum ConvertStep {
+    /// renames a git directory and repairs the links of its linked worktrees.
+    MoveGitDir {
+        from: PathBuf,
+        to: PathBuf,
+        linked_worktrees: Vec<PathBuf>,
+    },
+    WriteFile {
+        path: PathBuf,
+        contents: String,
+        previous: Option<String>,
+    },
+    RemoveFile {
+        path: PathBuf,
+        previous: String,
+    },
+    RemoveDir(PathBuf),
+    GitConfig {
+        git_dir: PathBuf,
+        key: String,
+        value: String,
+        previous: Option<String>,
+    },
+    /// registers a worktree without checking anything out into it.
+    AddWorktree {
+        git_dir: PathBuf,
+        path: PathBuf,
+        branch: Option<String>,
+    },
+    /// moves the named entries from one directory to another.
+    MoveEntries {
+        from: PathBuf,
+        to: PathBuf,
+        names: Vec<OsString>,
+    },
+    /// copies the index of the old working tree into the admin dir of a new worktree.
+    CopyIndexToWorktree {
+        index: PathBuf,
+        worktree: PathBuf,
+    },
+    CopyFile {
+        from: PathBuf,
+        to: PathBuf,
+    },
+    PruneWorktrees {
+        git_dir: PathBuf,
+    },
+}
+
+impl Step for ConvertStep {
+    fn describe(&self) -> String {
+        match self {
+            Self::MoveGitDir { from, to, .. } => format!("move {:?} to {:?}", from, to),
+            Self::WriteFile { path, contents, .. } => {
+                format!("write {:?} to {:?}", contents.trim(), path)
+            }
+            Self::RemoveFile { path, .. } => format!("remove {:?}", path),
+            Self::RemoveDir(path) => format!("remove empty directory {:?}", path),
+            Self::GitConfig { key, value, .. } => format!("git config {} {}", key, value),
+            Self::AddWorktree { path, branch, .. } => match branch {
+                Some(b) => format!("git worktree add --no-checkout {:?} {}", path, b),
+                None => format!("git worktree add --no-checkout --detach {:?}", path),
+            },
+            Self::MoveEntries { from, to, names } => {
+                format!("move {} entries from {:?} to {:?}", names.len(), from, to)
+            }
+            Self::CopyIndexToWorktree { index, worktree } => {
+                format!("copy index {:?} into worktree {:?}", index, worktree)
+            }
+            Self::CopyFile { from, to } => format!("copy {:?} to {:?}", from, to),
+            Self::PruneWorktrees { .. } => "git worktree prune".to_string(),
+        }
+    }
+
+    fn plan(&self) -> Vec<String> {
+        let quote = |p: &Path| exec::shell_quote(p.as_os_str());
+        let git = |git_dir: &Path, args: &[&str]| display_command(&git_command(git_dir, args));
+        match self {
+            Self::MoveGitDir {
+                from,
+                to,
+                linked_worktrees,
+            } => {
+                let mut lines = vec![format!("mv {} {}", quote(from), quote(to))];
+                lines.extend(repair_command(to, linked_worktrees).map(|c| display_command(&c)));
+                lines
+            }
+            Self::WriteFile { path, contents, .. } => {
+                vec![format!("write {}: {:?}", quote(path), contents)]
+            }
+            Self::RemoveFile { path, .. } => vec![format!("rm {}", quote(path))],
+            Self::RemoveDir(path) => vec![format!("rmdir {}", quote(path))],
+            Self::GitConfig {
+                git_dir,
+                key,
+                value,
+                ..
+            } => vec![git(git_dir, &["config", key, value])],
+            Self::AddWorktree {
+                git_dir,
+                path,
+                branch,
+            } => vec![display_command(&add_worktree_command(
+                git_dir,
+                path,
+                branch.as_deref(),
+            ))],
+            Self::MoveEntries { from, to, names } => vec![format!(
+                "mv {} {}",
+                names
+                    .iter()
+                    .map(|n| quote(&from.join(n)))
+                    .collect::<Vec<_>>()
+                    .join(" "),
+                quote(to)
+            )],
+            // the admin dir only exists once the worktree has been added.
+            Self::CopyIndexToWorktree { index, worktree } => vec![format!(
+                "cp {} <admin dir of {}>/index",
+                quote(index),
+                quote(worktree)
+            )],
+            Self::CopyFile { from, to } => vec![format!("cp {} {}", quote(from), quote(to))],
+            Self::PruneWorktrees { git_dir } => vec![git(git_dir, &["worktree", "prune"])],
+        }
+    }
+
+    fn apply(&self) -> Result<(), String> {
+        match self {
+            Self::MoveGitDir {
+                from,
+                to,
+                linked_worktrees,
+            } => {
+                fs::rename(from, to).map_err(|e| e.to_string())?;
+                repair_worktrees(to, linked_worktrees)
+            }
+            Self::WriteFile { path, contents, .. } => {
+                fs::write(path, contents).map_err(|e| e.to_string())
+            }
+            Self::RemoveFile { path, .. } => fs::remove_file(path).map_err(|e| e.to_string()),
+            Self::RemoveDir(path) => fs::remove_dir(path).map_err(|e| e.to_string()),
+            Self::GitConfig {
+                git_dir,
+                key,
+                value,
+                ..
+            } => run_git(git_dir, &["config", key, value]),
+            Self::AddWorktree {
+                git_dir,
+                path,
+                branch,
+            } => run_step(&mut add_worktree_command(git_dir, path, branch.as_deref())),
+            Self::MoveEntries { from, to, names } => move_entries(from, to, names),
+            Self::CopyIndexToWorktree { index, worktree } => {
+                let admin_dir = worktree_admin_dir(worktree)?;
+                fs::copy(index, admin_dir.join("index"))
+                    .map(|_| ())
+                    .map_err(|e| e.to_string())
+            }
+            Self::CopyFile { from, to } => {
+                fs::copy(from, to).map(|_| ()).map_err(|e| e.to_string())
+            }
+            Self::PruneWorktrees { git_dir } => run_git(git_dir, &["worktree", "prune"]),
+        }
+    }
+
+    fn undo(&self) -> Result<(), String> {
+        match self {
+            Self::MoveGitDir {
+                from,
+                to,
+                linked_worktrees,
+            } => {
+                fs::rename(to, from).map_err(|e| e.to_string())?;
+                repair_worktrees(from, linked_worktrees)
+            }
+            Self::WriteFile { path, previous, .. } => match previous {
+                Some(p) => fs::write(path, p).map_err(|e| e.to_string()),
+                None => fs::remove_file(path).map_err(|e| e.to_string()),
+            },
+            Self::RemoveFile { path, previous } => {
+                fs::write(path, previous).map_err(|e| e.to_string())
+            }
+            Self::RemoveDir(path) => fs::create_dir(path).map_err(|e| e.to_string()),
+            Self::GitConfig {
+                git_dir,
+                key,
+                previous,
+                ..
+            } => match previous {
+                Some(p) => run_git(git_dir, &["config", key, p]),
+                None => run_git(git_dir, &["config", "--unset", key]),
+            },
+            Self::AddWorktree { git_dir, path, .. } => run_git(
+                git_dir,
+                &["worktree", "remove", "--force", &path.to_string_lossy()],
+            ),
+            Self::MoveEntries { from, to, names } => move_entries(to, from, names),
+            // the admin dir is removed along with the worktree.
+            Self::CopyIndexToWorktree { .. } => Ok(()),
+            Self::CopyFile { to, .. } => fs::remove_file(to).map_err(|e| e.to_string()),
+            Self::PruneWorktrees { .. } => Ok(()),
+        }
+    }
+
+    fn keys(&self) -> Vec<PathBuf> {
+        match self {
+            Self::MoveGitDir {
+                from,
+                to,
+                linked_worktrees,
+            } => [from, to]
+                .into_iter()
+                .chain(linked_worktrees)
+                .cloned()
+                .collect(),
+            Self::WriteFile { path, .. }
+            | Self::RemoveFile { path, .. }
+            | Self::RemoveDir(path)
+            | Self::AddWorktree { path, .. } => vec![path.clone()],
+            Self::GitConfig { git_dir, .. } | Self::PruneWorktrees { git_dir } => {
+                vec![git_dir.clone()]
+            }
+            Self::MoveEntries { from, to, .. } | Self::CopyFile { from, to } => {
+                vec![from.clone(), to.clone()]
+            }
+            Self::CopyIndexToWorktree { worktree, .. } => vec![worktree.clone()],
+        }
+    }
+}
+
+/// an ordered set of steps that restructure a repo, which can be previewed before running.
+pub type Conversion = Transaction<ConvertStep>;
+
+/// plans the conversion of an ordinary clone at `repo` into the `.bare` worktree layout, with the
+/// existing working tree (including uncommitted changes and untracked files) becoming the first
+/// worktree in `repo/<worktree_dir_name>`.
+/// `dir_name_for_branch` turns the checked out branch name into the worktree directory name.
+pub fn to_worktree_repo(
+    repo: &Path,
+    dir_name_for_branch: impl Fn(&str) -> String,
+) -> Result<Conversion, String> {
+    let git_dir = repo.join(".git");
+    let bare_dir = repo.join(".bare");
+
+    if !git_dir.is_dir() {
+        return Err(format!("{:?} is not an ordinary git clone.", repo));
+    }
+    if RepoLayout::detect(repo).is_some() || bare_dir.exists() {
+        return Err(format!("{:?} is already a worktree repo.", repo));
+    }
+
+    let branch = git_output(repo, &["symbolic-ref", "--short", "-q", "HEAD"]);
+    let worktree_dir_name = dir_name_for_branch(branch.as_deref().unwrap_or("detached"));
+    if worktree_dir_name.is_empty() || repo.join(&worktree_dir_name).exists() {
+        return Err(format!(
+            "Cannot use {:?} as the worktree directory, as it already exists in {:?}.",
+            worktree_dir_name, repo
+        ));
+    }
+
+    let has_index = git_dir.join("index").exists();
+    let linked_worktrees: Vec<PathBuf> = git::linked_worktrees(repo)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|w| w.path)
+        .collect();
+    let worktree_path = repo.join(worktree_dir_name);
+
+    let mut names: Vec<OsString> = read_entry_names(repo)?
+        .into_iter()
+        .filter(|n| n != ".git")
+        .collect();
+    names.sort();
+
+    let mut steps = vec![
+        ConvertStep::MoveGitDir {
+            from: git_dir,
+            to: bare_dir.clone(),
+            linked_worktrees,
+        },
+        ConvertStep::GitConfig {
+            git_dir: bare_dir.clone(),
+            key: "core.bare".to_string(),
+            value: "true".to_string(),
+            previous: git_output(repo, &["config", "core.bare"]),
+        },
+        ConvertStep::WriteFile {
+            path: repo.join(".git"),
+            contents: "gitdir: ./.bare".to_string(),
+            previous: None,
+        },
+    ];
+
+    let has_origin = git_output(repo, &["config", "remote.origin.url"]).is_some();
+    let previous_refspec = git_output(repo, &["config", "remote.origin.fetch"]);
+    if has_origin && previous_refspec.as_deref() != Some(FETCH_REFSPEC) {
+        steps.push(ConvertStep::GitConfig {
+            git_dir: bare_dir.clone(),
+            key: "remote.origin.fetch".to_string(),
+            value: FETCH_REFSPEC.to_string(),
+            previous: previous_refspec,
+        });
+    }
+
+    steps.push(ConvertStep::AddWorktree {
+        git_dir: bare_dir.clone(),
+        path: worktree_path.clone(),
+        branch,
+    });
+    steps.push(ConvertStep::MoveEntries {
+        from: repo.to_path_buf(),
+        to: worktree_path.clone(),
+        names,
+    });
+    if has_index {
+        steps.push(ConvertStep::CopyIndexToWorktree {
+            index: bare_dir.join("index"),
+            worktree: worktree_path,
+        });
+    }
+
+    Ok(Conversion::new(
+        format!("Convert {:?} to a worktree repo", repo),
+        repo.to_path_buf(),
+        steps,
+    ))
+}
+
+/// plans the reverse conversion, turning a `.bare` worktree repo at `repo` with a single worktree
+/// back into an ordinary clone whose working tree is the contents of that worktree.
+pub fn to_normal_repo(repo: &Path) -> Result<Conversion, String> {
+    if RepoLayout::detect(repo) != Some(RepoLayout::DotBare) {
+        return Err(format!(
+            "{:?} is not a worktree repo using the .bare layout.",
+            repo
+        ));
+    }
+
+    let bare_dir = repo.join(".bare");
+    let worktrees = git::linked_worktrees(&bare_dir)
+        .ok_or_else(|| format!("Could not list the worktrees of {:?}.", repo))?;
+    let [worktree] = worktrees.as_slice() else {
+        return Err(format!(
+            "{:?} has {} worktrees, but must have exactly one to be converted. Delete the others first.",
+            repo,
+            worktrees.len()
+        ));
+    };
+
+    let admin_dir = worktree_admin_dir(&worktree.path)?;
+    let worktree_git_file = worktree.path.join(".git");
+    let worktree_git_file_contents =
+        fs::read_to_string(&worktree_git_file).map_err(|e| e.to_string())?;
+    let repo_git_file = repo.join(".git");
+    let repo_git_file_contents = fs::read_to_string(&repo_git_file).map_err(|e| e.to_string())?;
+
+    let existing: Vec<OsString> = read_entry_names(repo)?;
+    let mut names: Vec<OsString> = read_entry_names(&worktree.path)?
+        .into_iter()
+        .filter(|n| n != ".git")
+        .collect();
+    names.sort();
+
+    if let Some(clash) = names.iter().find(|n| existing.contains(n)) {
+        return Err(format!(
+            "Cannot move {:?} out of the worktree, as {:?} already exists in {:?}.",
+            clash, clash, repo
+        ));
+    }
+
+    let head = match (&worktree.branch, &worktree.head) {
+        (Some(branch), _) => format!("ref: refs/heads/{}\n", branch),
+        (None, Some(sha)) => format!("{}\n", sha),
+        (None, None) => return Err(format!("Could not read HEAD of {:?}.", worktree.path)),
+    };
+
+    let mut steps = vec![
+        ConvertStep::RemoveFile {
+            path: repo_git_file,
+            previous: repo_git_file_contents,
+        },
+        ConvertStep::RemoveFile {
+            path: worktree_git_file,
+            previous: worktree_git_file_contents,
+        },
+        ConvertStep::MoveEntries {
+            from: worktree.path.clone(),
+            to: repo.to_path_buf(),
+            names,
+        },
+    ];
+    steps.push(ConvertStep::RemoveDir(worktree.path.clone()));
+    if admin_dir.join("index").exists() {
+        steps.push(ConvertStep::CopyFile {
+            from: admin_dir.join("index"),
+            to: bare_dir.join("index"),
+        });
+    }
+    steps.push(ConvertStep::WriteFile {
+        path: bare_dir.join("HEAD"),
+        contents: head,
+        previous: fs::read_to_string(bare_dir.join("HEAD")).ok(),
+    });
+    steps.push(ConvertStep::GitConfig {
+        git_dir: bare_dir.clone(),
+        key: "core.bare".to_string(),
+        value: "false".to_string(),
+        previous: Some("true".to_string()),
+    });
+    steps.push(ConvertStep::MoveGitDir {
+        from: bare_dir,
+        to: repo.join(".git"),
+        linked_worktrees: vec![],
+    });
+    steps.push(ConvertStep::PruneWorktrees {
+        git_dir: repo.join(".git"),
+    });
+
+    Ok(Conversion::new(
+        format!("Convert {:?} to an ordinary repo", repo),
+        repo.to_path_buf(),
+        steps,
+    ))
+}
+
+/// reads the admin dir of a linked worktree from its `.git` file.
+fn worktree_admin_dir(worktree: &Path) -> Result<PathBuf, String> {
+    let contents = fs::read_to_string(worktree.join(".git")).map_err(|e| e.to_string())?;
+    let Some(gitdir) = contents.trim().strip_prefix("gitdir: ") else {
+        return Err(format!(
+            "{:?} does not contain a gitdir.",
+            worktree.join(".git")
+        ));
+    };
+    Ok(worktree.join(gitdir))
+}
+
+fn read_entry_names(dir: &Path) -> Result<Vec<OsString>, String> {
+    let contents = dir.read_dir().map_err(|e| e.to_string())?;
+    Ok(contents
+        .filter_map(|d| d.ok())
+        .map(|d| d.file_name())
+        .collect())
+}
+
+/// moves the named entries between directories. if any entry cannot be moved, the entries that
+/// were already moved are put back so the step either happens completely or not at all.
+fn move_entries(from: &Path, to: &Path, names: &[OsString]) -> Result<(), String> {
+    for (i, name) in names.iter().enumerate() {
+        if let Err(e) = fs::rename(from.join(name), to.join(name)) {
+            for moved in names[..i].iter() {
+                let _ = fs::rename(to.join(moved), from.join(moved));
+            }
+            return Err(format!("could not move {:?}: {}", name, e));
+        }
+    }
+    Ok(())
+}
+
+fn repair_worktrees(git_dir: &Path, worktrees: &[PathBuf]) -> Result<(), String> {
+    match repair_command(git_dir, worktrees) {
+        Some(mut cmd) => run_step(&mut cmd),
+        None => Ok(()),
+    }
+}
+
+/// the command fixing the links between `git_dir` and its linked worktrees after it has moved.
+fn repair_command(git_dir: &Path, worktrees: &[PathBuf]) -> Option<Command> {
+    if worktrees.is_empty() {
+        return None;
+    }
+    let mut cmd = git_command(git_dir, &["worktree", "repair"]);
+    cmd.args(worktrees);
+    Some(cmd)
+}
+
+/// the command registering a worktree at `path` with nothing checked out, on `branch` if there is
+/// one and detached otherwise.
+fn add_worktree_command(git_dir: &Path, path: &Path, branch: Option<&str>) -> Command {
+    let mut cmd = git_command(git_dir, &["worktree", "add", "--no-checkout"]);
+    match branch {
+        Some(b) => cmd.arg(path).arg(b),
+        None => cmd.arg("--detach").arg(path),
+    };
+    cmd
+}
+
+fn git_command(git_dir: &Path, args: &[&str]) -> Command {
+    let mut cmd = Command::new("git");
+    cmd.arg("--git-dir").arg(git_dir).args(args);
+    cmd
+}
+
+fn run_git(git_dir: &Path, args: &[&str]) -> Result<(), String> {
+    run_step(&mut git_command(git_dir, args))
+}
+
+fn run_step(cmd: &mut Command) -> Result<(), String> {
+    let output = cmd
+        .run()
+        .map_err(|e| format!("Failed to start 'git' process: {}", e))?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+    }
+}
+
+/// runs a read-only git query in `repo`, returning its trimmed stdout if it succeeded.
+fn git_output(repo: &Path, args: &[&str]) -> Option<String> {
+    let output = Command::new("git")
+        .current_dir(repo)
+        .args(args)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let out = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if out.is_empty() { None } else { Some(out) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{TempDir, git};
+
+    /// a clone of a repo with two commits on `main`, with a modified file, a staged change and an
+    /// untracked file.
+    fn dirty_clone(tmp: &TempDir) -> PathBuf {
+        let origin = tmp.0.join("origin");
+        fs::create_dir_all(origin.join("dir")).unwrap();
+        git(&origin, &["init", "-q", "-b", "main"]);
+        for (file, contents) in [("a.txt", "one\n"), ("dir/b.txt", "two\n")] {
+            fs::write(origin.join(file), contents).unwrap();
+            git(&origin, &["add", file]);
+            git(&origin, &["commit", "-q", "-m", file]);
+        }
+        git(&tmp.0, &["clone", "-q", "origin", "repo"]);
+
+        let repo = tmp.0.join("repo");
+        fs::write(repo.join("a.txt"), "changed\n").unwrap();
+        fs::write(repo.join("dir/b.txt"), "staged\n").unwrap();
+        git(&repo, &["add", "dir/b.txt"]);
+        fs::write(repo.join("untracked.txt"), "new\n").unwrap();
+        repo
+    }
+
+    /// every file in `dir` outside the git directory, with its contents.
+    fn files(dir: &Path) -> Vec<(PathBuf, String)> {
+        fn walk(root: &Path, dir: &Path, files: &mut Vec<(PathBuf, String)>) {
+            for entry in dir.read_dir().unwrap().map(|e| e.unwrap()) {
+                let path = entry.path();
+                if entry.file_name() == ".git" || entry.file_name() == ".bare" {
+                    continue;
+                } else if path.is_dir() {
+                    walk(root, &path, files);
+                } else {
+                    let contents = fs::read_to_string(&path).unwrap();
+                    files.push((path.strip_prefix(root).unwrap().to_path_buf(), contents));
+                }
+            }
+        }
+        let mut files = vec![];
+        walk(dir, dir, &mut files);
+        files.sort();
+        files
+    }
+
+    /// what git says has changed in the working tree at `dir`, and what it has checked out.
+    fn state(dir: &Path) -> (String, String, String) {
+        (
+            git(dir, &["status", "--porcelain", "--untracked-files=all"]),
+            git(dir, &["rev-parse", "HEAD"]),
+            git(dir, &["rev-parse", "--abbrev-ref", "HEAD"]),
+        )
+    }
+
+    fn to_worktrees(repo: &Path) -> Result<Vec<String>, Vec<String>> {
+        to_worktree_repo(repo, |b| b.to_string())
+            .unwrap()
+            .run_unrecorded()
+    }
+
+    #[test]
+    fn converts_a_dirty_clone_keeping_its_changes() {
+        let tmp = TempDir::new();
+        let repo = dirty_clone(&tmp);
+        let (before_files, before_state) = (files(&repo), state(&repo));
+
+        to_worktrees(&repo).unwrap();
+
+        assert_eq!(RepoLayout::detect(&repo), Some(RepoLayout::DotBare));
+        let worktree = repo.join("main");
+        assert_eq!(files(&worktree), before_files);
+        assert_eq!(state(&worktree), before_state);
+        assert_eq!(
+            git(&repo, &["config", "remote.origin.fetch"]),
+            FETCH_REFSPEC
+        );
+    }
+
+    #[test]
+    fn converts_a_detached_head() {
+        let tmp = TempDir::new();
+        let repo = dirty_clone(&tmp);
+        git(&repo, &["checkout", "-q", "--detach"]);
+        let before_state = state(&repo);
+        assert_eq!(before_state.2, "HEAD");
+
+        to_worktrees(&repo).unwrap();
+
+        assert_eq!(state(&repo.join("detached")), before_state);
+    }
+
+    #[test]
+    fn keeps_existing_linked_worktrees_working() {
+        let tmp = TempDir::new();
+        let repo = dirty_clone(&tmp);
+        let feature = tmp.0.join("feature");
+        git(
+            &repo,
+            &["worktree", "add", "-q", "-b", "feature", "../feature"],
+        );
+        fs::write(feature.join("a.txt"), "feature\n").unwrap();
+        let before_state = state(&feature);
+
+        to_worktrees(&repo).unwrap();
+
+        assert_eq!(state(&feature), before_state);
+        assert_eq!(
+            PathBuf::from(git(&feature, &["rev-parse", "--git-common-dir"])),
+            repo.join(".bare")
+        );
+        assert_eq!(git::linked_worktrees(&repo.join(".bare")).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn converting_there_and_back_restores_the_clone() {
+        let tmp = TempDir::new();
+        let repo = dirty_clone(&tmp);
+        let (before_files, before_state) = (files(&repo), state(&repo));
+
+        to_worktrees(&repo).unwrap();
+        to_normal_repo(&repo).unwrap().run_unrecorded().unwrap();
+
+        assert!(repo.join(".git").is_dir());
+        assert!(!repo.join(".bare").exists());
+        assert_eq!(files(&repo), before_files);
+        assert_eq!(state(&repo), before_state);
+        assert_eq!(git(&repo, &["config", "core.bare"]), "false");
+        assert!(git::linked_worktrees(&repo).unwrap().is_empty());
+    }
+
+    #[test]
+    fn a_failed_conversion_to_worktrees_is_undone() {
+        let tmp = TempDir::new();
+        let repo = dirty_clone(&tmp);
+        let (before_files, before_state) = (files(&repo), state(&repo));
+        let conversion = to_worktree_repo(&repo, |b| b.to_string()).unwrap();
+        // the worktree directory appears after planning, so adding the worktree fails after the
+        // git directory has already been moved and reconfigured.
+        fs::create_dir(repo.join("main")).unwrap();
+        fs::write(repo.join("main/blocker"), "").unwrap();
+
+        assert!(conversion.run_unrecorded().is_err());
+
+        fs::remove_dir_all(repo.join("main")).unwrap();
+        assert!(repo.join(".git").is_dir());
+        assert!(!repo.join(".bare").exists());
+        assert_eq!(files(&repo), before_files);
+        assert_eq!(state(&repo), before_state);
+        assert!(git_output(&repo, &["config", "core.bare"]).is_some_and(|b| b == "false"));
+    }
+
+    #[test]
+    fn a_failed_conversion_to_a_normal_repo_is_undone() {
+        let tmp = TempDir::new();
+        let repo = dirty_clone(&tmp);
+        to_worktrees(&repo).unwrap();
+        let worktree = repo.join("main");
+        let (before_files, before_state) = (files(&repo), state(&worktree));
+        let conversion = to_normal_repo(&repo).unwrap();
+        // `dir` cannot be moved onto a directory that is not empty, which fails after `a.txt`
+        // has already been moved out of the worktree.
+        fs::create_dir(repo.join("dir")).unwrap();
+        fs::write(repo.join("dir/blocker"), "").unwrap();
+
+        assert!(conversion.run_unrecorded().is_err());
+
+        fs::remove_dir_all(repo.join("dir")).unwrap();
+        assert_eq!(RepoLayout::detect(&repo), Some(RepoLayout::DotBare));
+        assert_eq!(files(&repo), before_files);
+        assert_eq!(state(&worktree), before_state);
+    }
+}
@@ -0,0 +1,444 @@
+use std::{
+    cell::{Cell, RefCell},
+    ffi::OsStr,
+    io::{self, Read},
+    os::unix::process::{CommandExt as _, ExitStatusExt},
+    path::{Path, PathBuf},
+    process::{Command, ExitStatus, Output, Stdio},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, Sender, TryRecvError},
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::askpass;
+
+/// how long a cancelled command is given to clean up after SIGTERM before it is killed.
+const KILL_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// something that happened while a task was running.
+#[derive(Debug)]
+enum TaskEvent {
+    /// a complete line of output from a command, or the command line itself.
+    Line(String),
+    /// a progress update, which git rewrites in place with `\r`.
+    Progress(String),
+    /// the task finished with these summary lines, having run these commands.
+    Finished(Result<Vec<String>, Vec<String>>, Vec<CommandRecord>),
+}
+
+/// what commands run on a task's thread report to.
+#[derive(Clone)]
+struct TaskContext {
+    events: Sender<TaskEvent>,
+    cancelled: Arc<AtomicBool>,
+}
+
+thread_local! {
+    /// set on the thread of a running task, so that commands started with `CommandExt::run`
+    /// stream their output to the task and stop when it is cancelled.
+    static CONTEXT: RefCell<Option<TaskContext>> = const { RefCell::new(None) };
+    /// set while running commands that must finish even if the task has been cancelled.
+    static IGNORING_CANCEL: Cell<bool> = const { Cell::new(false) };
+    /// the commands run on this thread inside `recording`.
+    static RECORDED: RefCell<Option<Vec<CommandRecord>>> = const { RefCell::new(None) };
+}
+
+/// a command that was run, kept for the history and the summary of its task.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CommandRecord {
+    pub cwd: Option<PathBuf>,
+    pub argv: Vec<String>,
+    /// the exit code, or none if the command was stopped by a signal.
+    pub exit_code: Option<i32>,
+    /// entries written before stdout was kept have none.
+    #[serde(default)]
+    pub stdout: String,
+    pub stderr: String,
+}
+
+impl CommandRecord {
+    /// the command as it could be typed into a shell.
+    pub fn command_line(&self) -> String {
+        let mut parts = vec![];
+        if let Some(ref cwd) = self.cwd {
+            parts.push(format!("cd {} &&", shell_quote(cwd.as_os_str())));
+        }
+        parts.extend(self.argv.iter().map(|a| shell_quote(OsStr::new(a))));
+        parts.join(" ")
+    }
+}
+
+/// an operation running on a background thread, which produces summary lines when it finishes.
+#[derive(Debug)]
+pub struct Task {
+    pub title: String,
+    /// every line of output from the commands run so far.
+    pub log: Vec<String>,
+    /// the latest progress line, e.g. `Receiving objects:  45% (450/1000)`.
+    pub progress: Option<String>,
+    started: Instant,
+    /// how long the task took, once it has finished.
+    finished_in: Option<Duration>,
+    events: Receiver<TaskEvent>,
+    cancelled: Arc<AtomicBool>,
+    result: Option<Result<Vec<String>, Vec<String>>>,
+    /// the commands the task ran, once it has finished.
+    pub commands: Vec<CommandRecord>,
+}
+
+impl Task {
+    /// runs `f` on a new thread.
+    pub fn spawn(
+        title: String,
+        f: impl FnOnce() -> Result<Vec<String>, Vec<String>> + Send + 'static,
+    ) -> Self {
+        let (tx, rx) = mpsc::channel();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let context = TaskContext {
+            events: tx.clone(),
+            cancelled: cancelled.clone(),
+        };
+        thread::spawn(move || {
+            CONTEXT.with(|c| *c.borrow_mut() = Some(context));
+            let (summary, commands) = recording(f);
+            let _ = tx.send(TaskEvent::Finished(summary, commands));
+        });
+
+        Self {
+            title,
+            log: vec![],
+            progress: None,
+            started: Instant::now(),
+            finished_in: None,
+            events: rx,
+            cancelled,
+            result: None,
+            commands: vec![],
+        }
+    }
+
+    /// takes in everything the task has reported since the last poll.
+    pub fn poll(&mut self) {
+        loop {
+            match self.events.try_recv() {
+                Ok(TaskEvent::Line(line)) => self.log.push(line),
+                Ok(TaskEvent::Progress(progress)) => self.progress = Some(progress),
+                Ok(TaskEvent::Finished(result, commands)) => {
+                    self.result = Some(result);
+                    self.commands = commands;
+                    self.finished_in = Some(self.started.elapsed());
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    // the thread panicked before it could report a result.
+                    if self.finished_in.is_none() {
+                        self.result = Some(Err(vec![format!(
+                            "FAILURE: {} stopped unexpectedly",
+                            self.title
+                        )]));
+                        self.finished_in = Some(self.started.elapsed());
+                    }
+                    break;
+                }
+            }
+        }
+    }
+
+    /// asks the running command to stop. the task finishes once it has.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished_in.is_some()
+    }
+
+    /// how long the task has been running, or how long it took once it has finished.
+    pub fn elapsed(&self) -> Duration {
+        self.finished_in.unwrap_or_else(|| self.started.elapsed())
+    }
+
+    /// the percentage in the latest progress line, if it has one.
+    pub fn percent(&self) -> Option<u16> {
+        let progress = self.progress.as_ref()?;
+        let (before, _) = progress.split_once('%')?;
+        let digits = before.rsplit(|c: char| !c.is_ascii_digit()).next()?;
+        digits.parse::<u16>().ok().map(|p| p.min(100))
+    }
+
+    /// the summary lines, once the task has finished. a cancelled task always counts as failed.
+    pub fn take_result(&mut self) -> Option<Result<Vec<String>, Vec<String>>> {
+        let result = self.result.take()?;
+        if !self.is_cancelled() {
+            return Some(result);
+        }
+        let mut summary = match result {
+            Ok(summary) | Err(summary) => summary,
+        };
+        summary.insert(0, format!("CANCELLED: {}", self.title));
+        Some(Err(summary))
+    }
+}
+
+/// `--progress` when running on a task's thread, so git reports progress even though its stderr
+/// is not a terminal.
+pub fn progress_flag() -> Option<&'static str> {
+    CONTEXT
+        .with(|c| c.borrow().is_some())
+        .then_some("--progress")
+}
+
+/// runs `f` with commands started on this thread no longer stopping when the task is cancelled,
+/// for cleaning up after a cancelled command.
+pub fn ignoring_cancel<T>(f: impl FnOnce() -> T) -> T {
+    let previous = IGNORING_CANCEL.with(|i| i.replace(true));
+    let result = f();
+    IGNORING_CANCEL.with(|i| i.set(previous));
+    result
+}
+
+/// runs `f`, returning the commands it ran with `CommandExt::run` along with its result.
+pub fn recording<T>(f: impl FnOnce() -> T) -> (T, Vec<CommandRecord>) {
+    let outer = RECORDED.with(|r| r.replace(Some(vec![])));
+    let result = f();
+    let recorded = RECORDED.with(|r| r.replace(outer)).unwrap_or_default();
+    // commands recorded by a nested call also belong to the outer one.
+    RECORDED.with(|r| {
+        if let Some(ref mut outer) = *r.borrow_mut() {
+            outer.extend(recorded.iter().cloned());
+        }
+    });
+    (result, recorded)
+}
+
+pub trait CommandExt {
+    /// like `Command::output`, but on a task's thread the output is streamed to the task as it is
+    /// written, and the command is stopped if the task is cancelled. progress lines are left out
+    /// of the returned output.
+    fn run(&mut self) -> io::Result<Output>;
+}
+
+impl CommandExt for Command {
+    fn run(&mut self) -> io::Result<Output> {
+        let output = match CONTEXT.with(|c| c.borrow().clone()) {
+            Some(context) => run_on_task(self, context)?,
+            None => self.output()?,
+        };
+        RECORDED.with(|r| {
+            if let Some(ref mut recorded) = *r.borrow_mut() {
+                recorded.push(CommandRecord {
+                    cwd: self.get_current_dir().map(Path::to_path_buf),
+                    argv: std::iter::once(self.get_program())
+                        .chain(self.get_args())
+                        .map(|a| a.to_string_lossy().into_owned())
+                        .collect(),
+                    exit_code: output.status.code(),
+                    stdout: String::from_utf8_lossy(&output.stdout).trim().to_string(),
+                    stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+                });
+            }
+        });
+        Ok(output)
+    }
+}
+
+/// runs `cmd` on a task's thread, streaming its output to the task and stopping it if the task is
+/// cancelled.
+fn run_on_task(cmd: &mut Command, context: TaskContext) -> io::Result<Output> {
+    let cancellable = !IGNORING_CANCEL.with(Cell::get);
+
+    if cancellable && context.cancelled.load(Ordering::SeqCst) {
+        return Ok(cancelled_output());
+    }
+
+    let _ = context
+        .events
+        .send(TaskEvent::Line(format!("$ {}", command_line(cmd))));
+
+    // the command gets its own session, and so its own process group, so that cancelling also
+    // stops anything it started, which would otherwise hold its output open. without a
+    // controlling terminal, git and ssh ask gpm for credentials instead of reading the terminal
+    // the TUI is drawn on.
+    unsafe {
+        cmd.pre_exec(|| {
+            libc::setsid();
+            Ok(())
+        });
+    }
+    let mut child = cmd
+        .envs(askpass::env())
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let stdout_reader = stream_lines(child.stdout.take(), context.events.clone());
+    let stderr_reader = stream_lines(child.stderr.take(), context.events.clone());
+
+    // git cleans up after itself on SIGTERM, e.g. removing a half finished clone, so it is
+    // given the chance to before being killed.
+    let group = -(child.id() as libc::pid_t);
+    let mut terminated_at: Option<Instant> = None;
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if cancellable && context.cancelled.load(Ordering::SeqCst) {
+            match terminated_at {
+                None => {
+                    unsafe { libc::kill(group, libc::SIGTERM) };
+                    terminated_at = Some(Instant::now());
+                }
+                Some(t) if t.elapsed() > KILL_TIMEOUT => {
+                    unsafe { libc::kill(group, libc::SIGKILL) };
+                }
+                Some(_) => {}
+            }
+        }
+        thread::sleep(Duration::from_millis(20));
+    };
+
+    Ok(Output {
+        status,
+        stdout: stdout_reader.join().unwrap_or_default(),
+        stderr: stderr_reader.join().unwrap_or_default(),
+    })
+}
+
+/// the output of a command that was not started because its task had been cancelled.
+fn cancelled_output() -> Output {
+    Output {
+        status: ExitStatus::from_raw(libc::SIGTERM),
+        stdout: vec![],
+        stderr: b"cancelled".to_vec(),
+    }
+}
+
+fn command_line(cmd: &Command) -> String {
+    std::iter::once(cmd.get_program())
+        .chain(cmd.get_args())
+        .map(OsStr::to_string_lossy)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// `cmd` as it could be typed into a shell, including the directory and environment it runs with.
+pub fn display_command(cmd: &Command) -> String {
+    let mut parts = vec![];
+    if let Some(dir) = cmd.get_current_dir() {
+        parts.push(format!("cd {} &&", shell_quote(dir.as_os_str())));
+    }
+    for (key, value) in cmd.get_envs() {
+        if let Some(value) = value {
+            parts.push(format!("{}={}", key.to_string_lossy(), shell_quote(value)));
+        }
+    }
+    parts.extend(
+        std::iter::once(cmd.get_program())
+            .chain(cmd.get_args())
+            .map(shell_quote),
+    );
+    parts.join(" ")
+}
+
+/// quotes `s` for a shell if it contains anything the shell would treat specially.
+pub fn shell_quote(s: &OsStr) -> String {
+    let s = s.to_string_lossy();
+    let plain = !s.is_empty()
+        && s.chars()
+            .all(|c| c.is_alphanumeric() || "-_./=:@+,%".contains(c));
+    if plain {
+        s.into_owned()
+    } else {
+        format!("'{}'", s.replace('\'', r"'\''"))
+    }
+}
+
+/// sends each line read from `pipe` to the task, returning everything but progress lines once
+/// the pipe is closed.
+fn stream_lines(
+    pipe: Option<impl Read + Send + 'static>,
+    events: Sender<TaskEvent>,
+) -> JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let Some(mut pipe) = pipe else {
+            return vec![];
+        };
+        let mut kept = vec![];
+        let mut line = vec![];
+        let mut buf = [0; 4096];
+        loop {
+            let n = match pipe.read(&mut buf) {
+                Ok(0) | Err(_) => break,
+                Ok(n) => n,
+            };
+            for &b in buf[..n].iter() {
+                match b {
+                    b'\n' => {
+                        let text = String::from_utf8_lossy(&line).into_owned();
+                        kept.extend_from_slice(&line);
+                        kept.push(b'\n');
+                        let _ = events.send(TaskEvent::Line(text));
+                        line.clear();
+                    }
+                    b'\r' => {
+                        let text = String::from_utf8_lossy(&line).into_owned();
+                        let _ = events.send(TaskEvent::Progress(text));
+                        line.clear();
+                    }
+                    _ => line.push(b),
+                }
+            }
+        }
+        if !line.is_empty() {
+            kept.extend_from_slice(&line);
+            let _ = events.send(TaskEvent::Line(String::from_utf8_lossy(&line).into_owned()));
+        }
+        kept
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn percent(progress: Option<&str>) -> Option<u16> {
+        let mut task = Task::spawn("test".to_string(), || Ok(vec![]));
+        task.progress = progress.map(str::to_string);
+        task.percent()
+    }
+
+    #[test]
+    fn reads_the_percentage_of_git_progress() {
+        assert_eq!(
+            percent(Some("Receiving objects:  45% (450/1000), 1.2 MiB")),
+            Some(45)
+        );
+        assert_eq!(
+            percent(Some("Resolving deltas: 100% (3/3), done.")),
+            Some(100)
+        );
+        assert_eq!(percent(Some("Updating files:   0% (0/12)")), Some(0));
+    }
+
+    #[test]
+    fn ignores_progress_without_a_percentage() {
+        assert_eq!(percent(None), None);
+        assert_eq!(percent(Some("Counting objects: 12")), None);
+        assert_eq!(percent(Some("Unpacking: %")), None);
+    }
+
+    #[test]
+    fn caps_the_percentage_at_100() {
+        assert_eq!(percent(Some("Checking: 250%")), Some(100));
+    }
+}
@@ -0,0 +1,329 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use crate::layout::RepoLayout;
+
+/// returns true if the directory at `path` is the root of a git repository, either a normal clone
+/// (`.git` dir), a linked worktree / gitdir redirect (`.git` file) or a worktree repo in any of the
+/// supported layouts.
+pub fn is_git_repo(path: &Path) -> bool {
+    path.join(".git").exists() || RepoLayout::detect(path).is_some()
+}
+
+/// a single entry from `git worktree list --porcelain`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WorktreeInfo {
+    pub path: PathBuf,
+    pub head: Option<String>,
+    /// the short branch name, if the worktree is not detached.
+    pub branch: Option<String>,
+    pub bare: bool,
+    pub detached: bool,
+    pub prunable: bool,
+}
+
+/// lists every worktree git knows about for the repo at `repo`, including the main worktree (or
+/// bare repo) as the first entry. returns None if git could not be run or failed.
+pub fn list_worktrees(repo: &Path) -> Option<Vec<WorktreeInfo>> {
+    let output = Command::new("git")
+        .current_dir(repo)
+        .arg("worktree")
+        .arg("list")
+        .arg("--porcelain")
+        .output()
+        .ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    Some(parse_worktree_porcelain(&String::from_utf8_lossy(
+        &output.stdout,
+    )))
+}
+
+/// lists only the linked worktrees of the repo at `repo`, skipping the main worktree, the bare
+/// repo itself and any worktrees whose directories no longer exist.
+pub fn linked_worktrees(repo: &Path) -> Option<Vec<WorktreeInfo>> {
+    let mut worktrees = list_worktrees(repo)?;
+    if !worktrees.is_empty() {
+        worktrees.remove(0);
+    }
+    worktrees.retain(|w| !w.bare && !w.prunable && w.path.is_dir());
+    Some(worktrees)
+}
+
+fn parse_worktree_porcelain(s: &str) -> Vec<WorktreeInfo> {
+    let mut worktrees = vec![];
+    let mut current: Option<WorktreeInfo> = None;
+
+    for line in s.lines() {
+        if line.is_empty() {
+            worktrees.extend(current.take());
+            continue;
+        }
+
+        let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+        if key == "worktree" {
+            worktrees.extend(current.take());
+            current = Some(WorktreeInfo {
+                path: PathBuf::from(value),
+                ..Default::default()
+            });
+            continue;
+        }
+
+        let Some(ref mut wt) = current else {
+            continue;
+        };
+        match key {
+            "HEAD" => wt.head = Some(value.to_string()),
+            "branch" => {
+                wt.branch = Some(
+                    value
+                        .strip_prefix("refs/heads/")
+                        .unwrap_or(value)
+                        .to_string(),
+                )
+            }
+            "bare" => wt.bare = true,
+            "detached" => wt.detached = true,
+            "prunable" => wt.prunable = true,
+            _ => {}
+        }
+    }
+    worktrees.extend(current);
+
+    worktrees
+}
+
+/// a remote of a repo, from `git remote -v`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Remote {
+    pub name: String,
+    pub url: String,
+    /// set if the remote is pushed to at a different url to the one it is fetched from.
+    pub push_url: Option<String>,
+}
+
+/// lists the remotes of the repo at `repo`, in the order git reports them. returns None if git
+/// could not be run or failed.
+pub fn list_remotes(repo: &Path) -> Option<Vec<Remote>> {
+    let output = Command::new("git")
+        .current_dir(repo)
+        .arg("remote")
+        .arg("-v")
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    let mut remotes: Vec<Remote> = vec![];
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        let Some((name, rest)) = line.split_once('\t') else {
+            continue;
+        };
+        let Some((url, kind)) = rest.rsplit_once(' ') else {
+            continue;
+        };
+        match remotes.iter_mut().find(|r| r.name == name) {
+            Some(remote) if kind == "(push)" && remote.url != url => {
+                remote.push_url = Some(url.to_string())
+            }
+            Some(_) => {}
+            None => remotes.push(Remote {
+                name: name.to_string(),
+                url: url.to_string(),
+                push_url: None,
+            }),
+        }
+    }
+    Some(remotes)
+}
+
+/// reads a single value from the config of the repo at `repo`.
+pub fn config_value(repo: &Path, key: &str) -> Option<String> {
+    let output = Command::new("git")
+        .current_dir(repo)
+        .arg("config")
+        .arg("--get")
+        .arg(key)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let value = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (!value.is_empty()).then_some(value)
+}
+
+/// returns true if `rev` names a commit in the repo at `repo`.
+pub fn rev_exists(repo: &Path, rev: &str) -> bool {
+    Command::new("git")
+        .current_dir(repo)
+        .arg("rev-parse")
+        .arg("--verify")
+        .arg("--quiet")
+        .arg(format!("{}^{{commit}}", rev))
+        .output()
+        .is_ok_and(|o| o.status.success())
+}
+
+/// where a new branch called `branch` should start, if the repo at `repo` has a base remote set
+/// with `checkout.defaultRemote`. this is the remote's branch of the same name if there is one,
+/// which the new branch should then track, or else the remote's default branch.
+pub fn base_start_point(repo: &Path, branch: &str) -> Option<(String, bool)> {
+    let base = config_value(repo, "checkout.defaultRemote")?;
+    let same_name = format!("{}/{}", base, branch);
+    if rev_exists(repo, &format!("refs/remotes/{}", same_name)) {
+        return Some((same_name, true));
+    }
+    let default_branch = format!("refs/remotes/{}/HEAD", base);
+    rev_exists(repo, &default_branch).then_some((default_branch, false))
+}
+
+/// returns true if the `.gitattributes` of the worktree at `worktree` sends any files through the
+/// git lfs filter.
+pub fn uses_lfs(worktree: &Path) -> bool {
+    fs::read_to_string(worktree.join(".gitattributes")).is_ok_and(|attributes| {
+        attributes
+            .lines()
+            .filter(|l| !l.trim_start().starts_with('#'))
+            .any(|l| l.split_whitespace().any(|attr| attr == "filter=lfs"))
+    })
+}
+
+/// counts of the submodules of a worktree that need attention.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SubmoduleStatus {
+    pub uninitialised: usize,
+    /// checked out at a different commit to the one recorded in the worktree.
+    pub out_of_date: usize,
+    pub conflicted: usize,
+}
+
+impl SubmoduleStatus {
+    /// a short description for the tree, or None if every submodule is up to date.
+    pub fn badge(&self) -> Option<String> {
+        let parts: Vec<String> = [
+            (self.uninitialised, "uninitialised"),
+            (self.out_of_date, "out of date"),
+            (self.conflicted, "conflicted"),
+        ]
+        .into_iter()
+        .filter(|(n, _)| *n > 0)
+        .map(|(n, desc)| format!("{} {}", n, desc))
+        .collect();
+        if parts.is_empty() {
+            None
+        } else {
+            Some(format!("[submodules: {}]", parts.join(", ")))
+        }
+    }
+}
+
+/// returns true if the worktree at `worktree` has submodules.
+pub fn has_submodules(worktree: &Path) -> bool {
+    worktree.join(".gitmodules").is_file()
+}
+
+/// the status of the submodules of the worktree at `worktree`, from `git submodule status`.
+/// returns None if it has no submodules or git could not be run.
+pub fn submodule_status(worktree: &Path) -> Option<SubmoduleStatus> {
+    if !has_submodules(worktree) {
+        return None;
+    }
+    let output = Command::new("git")
+        .current_dir(worktree)
+        .arg("submodule")
+        .arg("status")
+        .arg("--recursive")
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    let mut status = SubmoduleStatus::default();
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        match line.chars().next() {
+            Some('-') => status.uninitialised += 1,
+            Some('+') => status.out_of_date += 1,
+            Some('U') => status.conflicted += 1,
+            _ => {}
+        }
+    }
+    Some(status)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_bare_repo_with_linked_worktrees() {
+        let porcelain = "\
+worktree /src/repo/.bare
+bare
+
+worktree /src/repo/main
+HEAD 0123456789abcdef0123456789abcdef01234567
+branch refs/heads/main
+
+worktree /src/repo/feature/login
+HEAD 89abcdef0123456789abcdef0123456789abcdef
+branch refs/heads/feature/login
+";
+        let worktrees = parse_worktree_porcelain(porcelain);
+        assert_eq!(worktrees.len(), 3);
+        assert_eq!(worktrees[0].path, PathBuf::from("/src/repo/.bare"));
+        assert!(worktrees[0].bare);
+        assert_eq!(worktrees[1].branch.as_deref(), Some("main"));
+        assert_eq!(
+            worktrees[1].head.as_deref(),
+            Some("0123456789abcdef0123456789abcdef01234567")
+        );
+        assert_eq!(worktrees[2].path, PathBuf::from("/src/repo/feature/login"));
+        assert_eq!(worktrees[2].branch.as_deref(), Some("feature/login"));
+    }
+
+    #[test]
+    fn parses_detached_and_prunable_worktrees() {
+        let porcelain = "\
+worktree /src/repo
+HEAD 0123456789abcdef0123456789abcdef01234567
+branch refs/heads/main
+
+worktree /tmp/detached
+HEAD 89abcdef0123456789abcdef0123456789abcdef
+detached
+
+worktree /tmp/gone
+HEAD 89abcdef0123456789abcdef0123456789abcdef
+branch refs/heads/gone
+prunable gitdir file points to non-existent location
+";
+        let worktrees = parse_worktree_porcelain(porcelain);
+        assert_eq!(worktrees.len(), 3);
+        assert!(worktrees[1].detached);
+        assert_eq!(worktrees[1].branch, None);
+        assert!(worktrees[2].prunable);
+        assert!(!worktrees[0].prunable && !worktrees[0].detached);
+    }
+
+    #[test]
+    fn keeps_spaces_in_paths() {
+        let worktrees = parse_worktree_porcelain("worktree /src/my repo\nbare\n");
+        assert_eq!(worktrees.len(), 1);
+        assert_eq!(worktrees[0].path, PathBuf::from("/src/my repo"));
+    }
+
+    #[test]
+    fn parses_nothing_from_empty_output() {
+        assert!(parse_worktree_porcelain("").is_empty());
+    }
+}
@@ -0,0 +1,239 @@
+use std::{collections::HashMap, path::PathBuf};
+
+/// how a git repository link was written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GitUrlKind {
+    /// `scheme://[user@]host[:port]/path`, for https, http, ssh and git.
+    Url,
+    /// `[user@]host:path`, as used by ssh.
+    Scp,
+    /// `file:///path`.
+    File,
+    /// a plain path on the local filesystem.
+    Local,
+}
+
+/// a parsed git repository link.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GitUrl {
+    pub kind: GitUrlKind,
+    /// the host, without any user or port. None for local repositories.
+    pub host: Option<String>,
+    /// the path of the repository on the host, without a leading slash or `.git` suffix.
+    pub path: String,
+}
+
+impl GitUrl {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let s = s.trim();
+        let invalid = || format!("Could not interpret '{}' as git repository link.", s);
+        if s.is_empty() {
+            return Err(invalid());
+        }
+
+        let (kind, host, path) = if let Some(path) = s.strip_prefix("file://") {
+            (GitUrlKind::File, None, path)
+        } else if let Some((scheme, rest)) = s.split_once("://") {
+            if !matches!(
+                scheme,
+                "https" | "http" | "ssh" | "git" | "git+ssh" | "ssh+git"
+            ) {
+                return Err(invalid());
+            }
+            let (authority, path) = rest.split_once('/').ok_or_else(invalid)?;
+            (GitUrlKind::Url, Some(strip_user_and_port(authority)), path)
+        } else if let Some((authority, path)) = scp_parts(s) {
+            (GitUrlKind::Scp, Some(strip_user_and_port(authority)), path)
+        } else {
+            (GitUrlKind::Local, None, s)
+        };
+
+        if host.as_deref() == Some("") {
+            return Err(invalid());
+        }
+
+        let path = path.trim_end_matches('/');
+        let path = path.strip_suffix(".git").unwrap_or(path);
+        let path = match kind {
+            GitUrlKind::Url | GitUrlKind::Scp => path.trim_start_matches('/'),
+            GitUrlKind::File | GitUrlKind::Local => path,
+        };
+
+        let url = Self {
+            kind,
+            host,
+            path: path.to_string(),
+        };
+        if url.repo_name().is_empty() {
+            return Err(invalid());
+        }
+        Ok(url)
+    }
+
+    /// the last component of the path, used as the default directory name.
+    pub fn repo_name(&self) -> &str {
+        self.path.rsplit('/').next().unwrap_or_default()
+    }
+
+    /// the `host/owner` directories the repo is placed in under a host-owner-repo project
+    /// directory. nested groups (e.g. gitlab subgroups) become nested directories. local links
+    /// have no host, so cannot be placed.
+    pub fn placement_dir(&self) -> Result<PathBuf, String> {
+        let Some(ref host) = self.host else {
+            return Err(format!(
+                "'{}' has no host, so cannot be placed by host and owner.",
+                self.path
+            ));
+        };
+        let mut dir = PathBuf::from(host);
+        let owner = self.path.rsplit_once('/').map_or("", |(owner, _)| owner);
+        for component in owner.split('/').filter(|c| !c.is_empty()) {
+            if component == "." || component == ".." {
+                return Err(format!("'{}' is not a valid repository path.", self.path));
+            }
+            dir.push(component);
+        }
+        Ok(dir)
+    }
+}
+
+/// expands a `name:path` shorthand into a full link using the configured templates, where `{path}`
+/// in the template is replaced with everything after the colon. links that do not start with a
+/// configured shorthand are returned unchanged.
+pub fn expand_shorthand(s: &str, shorthands: &HashMap<String, String>) -> String {
+    let s = s.trim();
+    let Some((name, path)) = s.split_once(':') else {
+        return s.to_string();
+    };
+    if path.starts_with("//") {
+        return s.to_string();
+    }
+    match shorthands.get(name) {
+        Some(template) if template.contains("{path}") => template.replace("{path}", path),
+        Some(template) => format!("{}{}", template, path),
+        None => s.to_string(),
+    }
+}
+
+/// splits an scp-style `[user@]host:path` link. the host must not contain a slash, which tells it
+/// apart from a local path containing a colon.
+fn scp_parts(s: &str) -> Option<(&str, &str)> {
+    let (authority, path) = s.split_once(':')?;
+    if authority.is_empty() || authority.contains('/') || path.is_empty() {
+        return None;
+    }
+    Some((authority, path))
+}
+
+fn strip_user_and_port(authority: &str) -> String {
+    let host = authority
+        .rsplit_once('@')
+        .map_or(authority, |(_, host)| host);
+    let host = host.split_once(':').map_or(host, |(host, _)| host);
+    host.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(kind: GitUrlKind, host: Option<&str>, path: &str) -> GitUrl {
+        GitUrl {
+            kind,
+            host: host.map(str::to_string),
+            path: path.to_string(),
+        }
+    }
+
+    #[test]
+    fn parses_urls() {
+        assert_eq!(
+            GitUrl::parse("https://github.com/owner/repo.git"),
+            Ok(url(GitUrlKind::Url, Some("github.com"), "owner/repo"))
+        );
+        assert_eq!(
+            GitUrl::parse("ssh://git@example.com:2222/group/sub/repo/"),
+            Ok(url(GitUrlKind::Url, Some("example.com"), "group/sub/repo"))
+        );
+        assert_eq!(
+            GitUrl::parse("  git://example.com/repo  "),
+            Ok(url(GitUrlKind::Url, Some("example.com"), "repo"))
+        );
+    }
+
+    #[test]
+    fn parses_scp_links() {
+        assert_eq!(
+            GitUrl::parse("git@github.com:owner/repo.git"),
+            Ok(url(GitUrlKind::Scp, Some("github.com"), "owner/repo"))
+        );
+        assert_eq!(
+            GitUrl::parse("host:/abs/repo"),
+            Ok(url(GitUrlKind::Scp, Some("host"), "abs/repo"))
+        );
+    }
+
+    #[test]
+    fn parses_local_paths() {
+        assert_eq!(
+            GitUrl::parse("file:///srv/git/repo.git"),
+            Ok(url(GitUrlKind::File, None, "/srv/git/repo"))
+        );
+        assert_eq!(
+            GitUrl::parse("/srv/git/repo.git"),
+            Ok(url(GitUrlKind::Local, None, "/srv/git/repo"))
+        );
+        // a colon after a slash is part of a path, not an scp host.
+        assert_eq!(
+            GitUrl::parse("./repos/a:b"),
+            Ok(url(GitUrlKind::Local, None, "./repos/a:b"))
+        );
+    }
+
+    #[test]
+    fn keeps_dots_in_repo_names() {
+        let url = GitUrl::parse("https://github.com/owner/v1.2.git").unwrap();
+        assert_eq!(url.repo_name(), "v1.2");
+    }
+
+    #[test]
+    fn rejects_invalid_links() {
+        for link in [
+            "",
+            "   ",
+            "ftp://example.com/repo",
+            "https:///repo",
+            "https://example.com",
+            "https://example.com/",
+        ] {
+            assert!(GitUrl::parse(link).is_err(), "{:?} should not parse", link);
+        }
+    }
+
+    #[test]
+    fn expands_shorthands() {
+        let shorthands = HashMap::from([
+            ("gh".to_string(), "git@github.com:".to_string()),
+            (
+                "work".to_string(),
+                "https://git.work/{path}.git".to_string(),
+            ),
+        ]);
+        assert_eq!(
+            expand_shorthand("gh:owner/repo", &shorthands),
+            "git@github.com:owner/repo"
+        );
+        assert_eq!(
+            expand_shorthand("work:team/repo", &shorthands),
+            "https://git.work/team/repo.git"
+        );
+        assert_eq!(
+            expand_shorthand("other:owner/repo", &shorthands),
+            "other:owner/repo"
+        );
+        assert_eq!(
+            expand_shorthand("gh://owner/repo", &shorthands),
+            "gh://owner/repo"
+        );
+    }
+}
@@ -0,0 +1,381 @@
+use std::{
+    ffi::OsString,
+    fs::{self, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{Mutex, PoisonError},
+};
+
+use ratatui::{
+    layout::{Constraint, Layout},
+    style::{Color, Modifier, Style},
+    text::Line,
+    widgets::{Block, Paragraph, Row, StatefulWidget, Table, Widget},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{config, exec::CommandRecord, naming};
+
+const HELP: &str = "/ filter | enter details | esc back";
+
+/// how large the history file grows before it is moved aside and a new one started. only the
+/// latest file and the one before it are kept.
+const MAX_FILE_SIZE: u64 = 1024 * 1024;
+
+/// held while an entry is written, since jobs finishing at once could otherwise both move the
+/// file aside.
+static WRITING: Mutex<()> = Mutex::new(());
+
+/// one operation gpm ran, as kept in the history file.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Entry {
+    /// when the operation finished, in seconds since the unix epoch.
+    pub at: u64,
+    pub action: String,
+    /// the project the operation was run on.
+    pub target: PathBuf,
+    pub success: bool,
+    pub commands: Vec<CommandRecord>,
+    /// every step of the operation, including those that ran no command. entries written before
+    /// steps were kept have none.
+    #[serde(default)]
+    pub steps: Vec<StepRecord>,
+    /// the summary lines shown when the operation finished.
+    pub summary: Vec<String>,
+}
+
+/// a step of an operation, whether or not it ran a command.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StepRecord {
+    pub description: String,
+    /// the commands the step runs and the files it changes, as listed by a dry run.
+    pub changes: Vec<String>,
+    /// DONE, FAILED, NOT RUN, ROLLED BACK or ROLLBACK FAILED.
+    pub outcome: String,
+}
+
+impl Entry {
+    /// whether `filter` appears in the action, target, summary or any command, ignoring case.
+    pub fn matches(&self, filter: &str) -> bool {
+        let filter = filter.to_lowercase();
+        let contains = |s: &str| s.to_lowercase().contains(&filter);
+        contains(&self.action)
+            || contains(&self.target.to_string_lossy())
+            || self.summary.iter().any(|l| contains(l))
+            || self
+                .steps
+                .iter()
+                .any(|s| contains(&s.description) || s.changes.iter().any(|c| contains(c)))
+            || self.commands.iter().any(|c| contains(&c.command_line()))
+    }
+
+    /// a single line with when the operation ran, whether it succeeded, and what it was.
+    pub fn headline(&self) -> String {
+        format!(
+            "{} {} {} ({:?})",
+            naming::format_timestamp(self.at),
+            if self.success { "✔" } else { "✘" },
+            self.action,
+            self.target
+        )
+    }
+
+    /// the summary, followed by every step with its outcome and what it changes.
+    pub fn details(&self) -> Vec<String> {
+        let mut lines = self.summary.clone();
+        if !self.steps.is_empty() {
+            lines.push(String::new());
+        }
+        for (i, step) in self.steps.iter().enumerate() {
+            lines.push(format!("{}. {}: {}", i + 1, step.outcome, step.description));
+            lines.extend(step.changes.iter().map(|c| format!("   {}", c)));
+        }
+        lines
+    }
+}
+
+/// the file every operation is appended to, one json object per line.
+pub fn history_file() -> Option<PathBuf> {
+    config::state_dir().map(|d| d.join("history.jsonl"))
+}
+
+/// where the history file is moved once it is full.
+fn previous_file(path: &Path) -> PathBuf {
+    let mut previous = OsString::from(path);
+    previous.push(".1");
+    PathBuf::from(previous)
+}
+
+/// appends an operation to the history file.
+pub fn record(
+    action: &str,
+    target: &Path,
+    result: &Result<Vec<String>, Vec<String>>,
+    commands: Vec<CommandRecord>,
+    steps: Vec<StepRecord>,
+) -> Result<(), String> {
+    let Some(path) = history_file() else {
+        return Err("Could not find a state directory for gpm.".to_string());
+    };
+    let (success, summary) = match result {
+        Ok(summary) => (true, summary.clone()),
+        Err(summary) => (false, summary.clone()),
+    };
+    let entry = Entry {
+        at: naming::now(),
+        action: action.to_string(),
+        target: target.to_path_buf(),
+        success,
+        commands,
+        steps,
+        summary,
+    };
+    let mut line = serde_json::to_string(&entry).map_err(|e| e.to_string())?;
+    line.push('\n');
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    append(&path, &line, MAX_FILE_SIZE)
+}
+
+/// appends `line` to the file at `path`, first moving the file aside if it would grow past
+/// `max_size`.
+fn append(path: &Path, line: &str, max_size: u64) -> Result<(), String> {
+    let _writing = WRITING.lock().unwrap_or_else(PoisonError::into_inner);
+    let size = fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+    if size > 0 && size + line.len() as u64 > max_size {
+        let previous = previous_file(path);
+        fs::rename(path, &previous)
+            .map_err(|e| format!("Failed to move {:?} to {:?}: {}", path, previous, e))?;
+    }
+    // other gpm processes may be writing too, so each entry is written in a single append.
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut f| f.write_all(line.as_bytes()))
+        .map_err(|e| format!("Failed to write to {:?}: {}", path, e))
+}
+
+/// every operation in the history file and the one moved aside before it, oldest first. lines
+/// that cannot be read are skipped.
+pub fn load() -> Vec<Entry> {
+    history_file().map(|p| read(&p)).unwrap_or_default()
+}
+
+fn read(path: &Path) -> Vec<Entry> {
+    [previous_file(path), path.to_path_buf()]
+        .iter()
+        .filter_map(|p| fs::read_to_string(p).ok())
+        .flat_map(|contents| {
+            contents
+                .lines()
+                .filter_map(|l| serde_json::from_str(l).ok())
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+#[derive(Debug, Default)]
+pub struct HistoryState {
+    /// every entry, newest first.
+    entries: Vec<Entry>,
+    filter: String,
+    /// whether keys typed go into the filter.
+    pub filtering: bool,
+    /// the selected entry, among those matching the filter.
+    idx: usize,
+}
+
+impl HistoryState {
+    pub fn load() -> Self {
+        let mut entries = load();
+        entries.reverse();
+        Self {
+            entries,
+            ..Default::default()
+        }
+    }
+
+    fn visible(&self) -> Vec<&Entry> {
+        self.entries
+            .iter()
+            .filter(|e| self.filter.is_empty() || e.matches(&self.filter))
+            .collect()
+    }
+
+    pub fn up(&mut self) {
+        self.idx = self.idx.saturating_sub(1);
+    }
+
+    pub fn down(&mut self) {
+        if self.idx + 1 < self.visible().len() {
+            self.idx += 1;
+        }
+    }
+
+    pub fn selected(&self) -> Option<&Entry> {
+        self.visible().get(self.idx).copied()
+    }
+
+    pub fn push_filter(&mut self, c: char) {
+        self.filter.push(c);
+        self.idx = 0;
+    }
+
+    pub fn pop_filter(&mut self) {
+        self.filter.pop();
+        self.idx = 0;
+    }
+}
+
+pub struct HistoryView {}
+
+impl StatefulWidget for HistoryView {
+    type State = HistoryState;
+
+    fn render(
+        self,
+        area: ratatui::prelude::Rect,
+        buf: &mut ratatui::prelude::Buffer,
+        state: &mut Self::State,
+    ) {
+        let visible = state.visible();
+        let block = Block::bordered().title(format!(
+            " History ({} of {}) ",
+            visible.len(),
+            state.entries.len()
+        ));
+        let inner = block.inner(area);
+        block.render(area, buf);
+        let [filter_area, table_area, help_area] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Fill(1),
+            Constraint::Length(1),
+        ])
+        .areas(inner);
+
+        let filter_style = if state.filtering {
+            Style::new().fg(Color::Yellow)
+        } else {
+            Style::new().fg(Color::DarkGray)
+        };
+        Paragraph::new(Line::styled(
+            format!("filter: {}", state.filter),
+            filter_style,
+        ))
+        .render(filter_area, buf);
+
+        if visible.is_empty() {
+            Paragraph::new("no operations").render(table_area, buf);
+        }
+        // keep the selected entry on screen.
+        let height = table_area.height as usize;
+        let skip = (state.idx + 1).saturating_sub(height);
+        let rows = visible.iter().enumerate().skip(skip).map(|(i, entry)| {
+            let (symbol, colour) = if entry.success {
+                ("✔", Color::LightGreen)
+            } else {
+                ("✘", Color::LightRed)
+            };
+            let mut row = Row::new(vec![
+                Line::raw(naming::format_timestamp(entry.at)),
+                Line::styled(symbol, Style::new().fg(colour)),
+                Line::raw(entry.action.clone()),
+                Line::raw(entry.target.to_string_lossy().into_owned()),
+            ]);
+            if i == state.idx {
+                row = row.style(Style::default().add_modifier(Modifier::BOLD | Modifier::REVERSED));
+            }
+            row
+        });
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Length(19),
+                Constraint::Length(1),
+                Constraint::Percentage(50),
+                Constraint::Fill(1),
+            ],
+        );
+        Widget::render(table, table_area, buf);
+
+        Paragraph::new(Line::styled(HELP, Style::new().fg(Color::DarkGray))).render(help_area, buf);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    fn entry(action: &str) -> Entry {
+        Entry {
+            at: 0,
+            action: action.to_string(),
+            target: PathBuf::from("/p/repo"),
+            success: true,
+            commands: vec![],
+            steps: vec![StepRecord {
+                description: "delete \"/p/repo\"".to_string(),
+                changes: vec!["rm -rf /p/repo".to_string()],
+                outcome: "DONE".to_string(),
+            }],
+            summary: vec![format!("SUCCESS: {}", action)],
+        }
+    }
+
+    fn line(entry: &Entry) -> String {
+        serde_json::to_string(entry).unwrap() + "\n"
+    }
+
+    fn actions(path: &Path) -> Vec<String> {
+        read(path).into_iter().map(|e| e.action).collect()
+    }
+
+    #[test]
+    fn full_file_is_moved_aside() {
+        let tmp = TempDir::new();
+        let path = tmp.0.join("history.jsonl");
+        let size = line(&entry("a")).len() as u64;
+        for action in ["a", "b", "c", "d", "e"] {
+            append(&path, &line(&entry(action)), size * 2).unwrap();
+        }
+        // only the last two files are kept.
+        assert_eq!(actions(&path), ["c", "d", "e"]);
+        assert_eq!(read(&previous_file(&path)).len(), 2);
+    }
+
+    #[test]
+    fn steps_without_commands_are_kept() {
+        let tmp = TempDir::new();
+        let path = tmp.0.join("history.jsonl");
+        append(&path, &line(&entry("Delete repo")), MAX_FILE_SIZE).unwrap();
+        let entries = read(&path);
+        assert_eq!(entries[0].steps[0].changes, ["rm -rf /p/repo"]);
+        assert!(entries[0].matches("RM -RF"));
+        assert_eq!(
+            entries[0].details(),
+            [
+                "SUCCESS: Delete repo",
+                "",
+                "1. DONE: delete \"/p/repo\"",
+                "   rm -rf /p/repo",
+            ]
+        );
+    }
+
+    #[test]
+    fn entries_from_before_steps_were_kept_still_load() {
+        let tmp = TempDir::new();
+        let path = tmp.0.join("history.jsonl");
+        fs::write(
+            &path,
+            "{\"at\":0,\"action\":\"old\",\"target\":\"/p\",\"success\":true,\"commands\":[],\
+             \"summary\":[]}\nnot json\n",
+        )
+        .unwrap();
+        assert_eq!(actions(&path), ["old"]);
+    }
+}
@@ -0,0 +1,401 @@
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Instant,
+};
+
+use ratatui::{
+    layout::{Constraint, Layout},
+    style::{Color, Modifier, Style},
+    text::Line,
+    widgets::{Block, Paragraph, Row, StatefulWidget, Table, TableState, Widget},
+};
+
+use crate::exec::Task;
+
+const HELP: &str = "enter log or summary | r retry | c cancel | C clear finished | esc back";
+
+/// the work a job does. it is kept so that a failed job can be run again.
+type Work = Arc<dyn Fn() -> Result<Vec<String>, Vec<String>> + Send + Sync>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Succeeded,
+    Failed,
+}
+
+impl JobStatus {
+    fn symbol(self) -> (&'static str, Color) {
+        match self {
+            Self::Queued => ("…", Color::DarkGray),
+            Self::Running => ("▶", Color::Yellow),
+            Self::Succeeded => ("✔", Color::LightGreen),
+            Self::Failed => ("✘", Color::LightRed),
+        }
+    }
+}
+
+pub struct Job {
+    pub title: String,
+    /// the project the job changes.
+    pub target: PathBuf,
+    /// the directories the job changes. a job does not start while another one changing any of
+    /// them, or anything inside or around them, is running.
+    keys: Vec<PathBuf>,
+    pub status: JobStatus,
+    /// the task of the latest run, which is kept once it has finished for its log.
+    pub task: Option<Task>,
+    /// the summary lines of the latest run.
+    pub summary: Vec<String>,
+    work: Work,
+}
+
+impl fmt::Debug for Job {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Job")
+            .field("title", &self.title)
+            .field("target", &self.target)
+            .field("keys", &self.keys)
+            .field("status", &self.status)
+            .field("task", &self.task)
+            .field("summary", &self.summary)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Job {
+    /// whether the job changes any of `keys`, or a directory inside or around one of them.
+    fn touches(&self, keys: &[PathBuf]) -> bool {
+        self.keys
+            .iter()
+            .any(|k| keys.iter().any(|other| overlaps(k, other)))
+    }
+
+    fn start(&mut self) {
+        let work = self.work.clone();
+        self.task = Some(Task::spawn(self.title.clone(), move || work()));
+        self.status = JobStatus::Running;
+    }
+}
+
+/// a finished job, shown for a little while without interrupting whatever is on screen.
+#[derive(Debug)]
+pub struct Notification {
+    pub text: String,
+    pub success: bool,
+    /// the project the job changed.
+    pub target: PathBuf,
+    pub at: Instant,
+}
+
+/// operations waiting to run or running in the background, and those that have finished.
+#[derive(Debug, Default)]
+pub struct JobQueue {
+    jobs: Vec<Job>,
+    idx: usize,
+    /// how far the jobs table is scrolled.
+    table_state: TableState,
+}
+
+impl JobQueue {
+    /// adds a job to the end of the queue. it starts once there is room under the limit and no
+    /// running job changes the same directories.
+    pub fn push(
+        &mut self,
+        title: String,
+        target: PathBuf,
+        keys: Vec<PathBuf>,
+        work: impl Fn() -> Result<Vec<String>, Vec<String>> + Send + Sync + 'static,
+    ) {
+        self.jobs.push(Job {
+            title,
+            target,
+            keys,
+            status: JobStatus::Queued,
+            task: None,
+            summary: vec![],
+            work: Arc::new(work),
+        });
+    }
+
+    /// collects the results of running jobs, then starts queued jobs until `limit` are running.
+    /// a queued job is held back while a running job, or one queued before it that is held back,
+    /// changes the same directories, so that jobs on the same repo run in the order they were
+    /// queued. returns a notification for each job that finished.
+    pub fn poll(&mut self, limit: usize) -> Vec<Notification> {
+        let mut notifications = vec![];
+        for job in self.jobs.iter_mut() {
+            let Some(ref mut task) = job.task else {
+                continue;
+            };
+            task.poll();
+            let Some(result) = task.take_result() else {
+                continue;
+            };
+            let success = result.is_ok();
+            job.status = if success {
+                JobStatus::Succeeded
+            } else {
+                JobStatus::Failed
+            };
+            job.summary = match result {
+                Ok(summary) | Err(summary) => summary,
+            };
+            notifications.push(Notification {
+                text: format!(
+                    "{} {}",
+                    if success { "Finished" } else { "Failed" },
+                    job.title
+                ),
+                success,
+                target: job.target.clone(),
+                at: Instant::now(),
+            });
+        }
+
+        let mut running = self.count(JobStatus::Running);
+        let mut claimed: Vec<PathBuf> = self
+            .jobs
+            .iter()
+            .filter(|j| j.status == JobStatus::Running)
+            .flat_map(|j| j.keys.iter().cloned())
+            .collect();
+        for job in self.jobs.iter_mut() {
+            if running >= limit {
+                break;
+            }
+            if job.status != JobStatus::Queued {
+                continue;
+            }
+            if !job.touches(&claimed) {
+                job.start();
+                running += 1;
+            }
+            claimed.extend(job.keys.iter().cloned());
+        }
+        notifications
+    }
+
+    /// whether any job is queued or running.
+    pub fn is_busy(&self) -> bool {
+        self.jobs
+            .iter()
+            .any(|j| matches!(j.status, JobStatus::Queued | JobStatus::Running))
+    }
+
+    pub fn count(&self, status: JobStatus) -> usize {
+        self.jobs.iter().filter(|j| j.status == status).count()
+    }
+
+    pub fn up(&mut self) {
+        self.idx = self.idx.saturating_sub(1);
+    }
+
+    pub fn down(&mut self) {
+        if self.idx + 1 < self.jobs.len() {
+            self.idx += 1;
+        }
+    }
+
+    pub fn selected(&self) -> Option<&Job> {
+        self.jobs.get(self.idx)
+    }
+
+    pub fn selected_mut(&mut self) -> Option<&mut Job> {
+        self.jobs.get_mut(self.idx)
+    }
+
+    /// queues the selected job again if it failed.
+    pub fn retry_selected(&mut self) {
+        if let Some(job) = self.jobs.get_mut(self.idx)
+            && job.status == JobStatus::Failed
+        {
+            job.status = JobStatus::Queued;
+            job.task = None;
+            job.summary = vec![];
+        }
+    }
+
+    /// cancels the selected job if it is running, or takes it out of the queue if it has not
+    /// started yet.
+    pub fn cancel_selected(&mut self) {
+        if let Some(job) = self.jobs.get_mut(self.idx) {
+            cancel(job);
+        }
+    }
+
+    /// cancels every running job and empties the queue.
+    pub fn cancel_all(&mut self) {
+        self.jobs.iter_mut().for_each(cancel);
+    }
+
+    /// forgets the jobs that have finished, whether they succeeded, failed or were cancelled.
+    pub fn clear_finished(&mut self) {
+        self.jobs
+            .retain(|j| matches!(j.status, JobStatus::Queued | JobStatus::Running));
+        self.idx = self.idx.min(self.jobs.len().saturating_sub(1));
+    }
+}
+
+/// whether `a` and `b` are the same directory, or one is inside the other.
+fn overlaps(a: &Path, b: &Path) -> bool {
+    a.starts_with(b) || b.starts_with(a)
+}
+
+fn cancel(job: &mut Job) {
+    match job.status {
+        JobStatus::Running => {
+            if let Some(ref task) = job.task {
+                task.cancel();
+            }
+        }
+        JobStatus::Queued => {
+            job.status = JobStatus::Failed;
+            job.summary = vec![format!("CANCELLED: {}", job.title)];
+        }
+        JobStatus::Succeeded | JobStatus::Failed => {}
+    }
+}
+
+pub struct JobsView {}
+
+impl StatefulWidget for JobsView {
+    type State = JobQueue;
+
+    fn render(
+        self,
+        area: ratatui::prelude::Rect,
+        buf: &mut ratatui::prelude::Buffer,
+        state: &mut Self::State,
+    ) {
+        let block = Block::bordered().title(format!(
+            " Jobs ({} running, {} queued) ",
+            state.count(JobStatus::Running),
+            state.count(JobStatus::Queued)
+        ));
+        let inner = block.inner(area);
+        block.render(area, buf);
+        let [table_area, help_area] =
+            Layout::vertical([Constraint::Fill(1), Constraint::Length(1)]).areas(inner);
+
+        if state.jobs.is_empty() {
+            Paragraph::new("no jobs").render(table_area, buf);
+        }
+        let rows = state.jobs.iter().map(|job| {
+            let (symbol, colour) = job.status.symbol();
+            let elapsed = match job.task {
+                Some(ref task) => format!("{}s", task.elapsed().as_secs()),
+                None => String::new(),
+            };
+            Row::new(vec![
+                Line::styled(symbol, Style::new().fg(colour)),
+                Line::raw(job.title.clone()),
+                Line::raw(elapsed).right_aligned(),
+            ])
+        });
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Length(1),
+                Constraint::Fill(1),
+                Constraint::Length(8),
+            ],
+        )
+        .row_highlight_style(Style::default().add_modifier(Modifier::BOLD | Modifier::REVERSED));
+        state.table_state.select(Some(state.idx));
+        StatefulWidget::render(table, table_area, buf, &mut state.table_state);
+
+        Paragraph::new(Line::styled(HELP, Style::new().fg(Color::DarkGray))).render(help_area, buf);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{thread, time::Duration};
+
+    use super::*;
+
+    fn push(queue: &mut JobQueue, title: &str, keys: &[&str]) {
+        queue.push(
+            title.to_string(),
+            PathBuf::from(keys[0]),
+            keys.iter().map(PathBuf::from).collect(),
+            || {
+                thread::sleep(Duration::from_millis(200));
+                Ok(vec![])
+            },
+        );
+    }
+
+    fn statuses(queue: &JobQueue) -> Vec<JobStatus> {
+        queue.jobs.iter().map(|j| j.status).collect()
+    }
+
+    #[test]
+    fn jobs_on_the_same_directories_run_one_at_a_time() {
+        let mut queue = JobQueue::default();
+        push(&mut queue, "clone", &["/p/repo"]);
+        push(
+            &mut queue,
+            "add worktree",
+            &["/p/repo/.bare", "/p/repo/feat"],
+        );
+        push(&mut queue, "other", &["/p/other"]);
+        push(&mut queue, "mirror", &["/p/other-2", "/cache/x.git"]);
+        push(&mut queue, "same mirror", &["/p/other-3", "/cache/x.git"]);
+        queue.poll(8);
+        assert_eq!(
+            statuses(&queue),
+            [
+                JobStatus::Running,
+                JobStatus::Queued,
+                JobStatus::Running,
+                JobStatus::Running,
+                JobStatus::Queued,
+            ]
+        );
+    }
+
+    #[test]
+    fn held_back_jobs_keep_their_order() {
+        let mut queue = JobQueue::default();
+        push(&mut queue, "first", &["/p/a"]);
+        push(&mut queue, "second", &["/p/a", "/p/b"]);
+        push(&mut queue, "third", &["/p/b"]);
+        queue.poll(8);
+        assert_eq!(
+            statuses(&queue),
+            [JobStatus::Running, JobStatus::Queued, JobStatus::Queued]
+        );
+    }
+
+    #[test]
+    fn the_limit_still_applies() {
+        let mut queue = JobQueue::default();
+        push(&mut queue, "a", &["/p/a"]);
+        push(&mut queue, "b", &["/p/b"]);
+        queue.poll(1);
+        assert_eq!(statuses(&queue), [JobStatus::Running, JobStatus::Queued]);
+    }
+
+    #[test]
+    fn clearing_forgets_every_finished_job() {
+        let mut queue = JobQueue::default();
+        for title in ["succeeded", "failed", "running", "queued"] {
+            push(&mut queue, title, &["/p/a"]);
+        }
+        for (job, status) in
+            queue
+                .jobs
+                .iter_mut()
+                .zip([JobStatus::Succeeded, JobStatus::Failed, JobStatus::Running])
+        {
+            job.status = status;
+        }
+        queue.clear_finished();
+        assert_eq!(statuses(&queue), [JobStatus::Running, JobStatus::Queued]);
+    }
+}
@@ -0,0 +1,208 @@
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::git;
+
+/// how the bare repo and worktrees of a worktree repo are arranged on disk.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "kebab-case")]
+pub enum RepoLayout {
+    /// `repo/.bare` is the bare repo, `repo/.git` is a file containing `gitdir: ./.bare`, and
+    /// worktrees live inside `repo/`.
+    #[default]
+    DotBare,
+    /// `repo/.git` is itself the bare repo, and worktrees live inside `repo/`.
+    DotGit,
+    /// `repo.git` is the bare repo, and worktrees live in a `repo/` directory next to it.
+    Sibling,
+}
+
+impl RepoLayout {
+    /// works out which layout the worktree repo at `path` uses, if it is one at all.
+    pub fn detect(path: &Path) -> Option<Self> {
+        if path.join(".bare").is_dir() {
+            return Some(Self::DotBare);
+        }
+
+        let dot_git = path.join(".git");
+        if dot_git.is_dir() && is_bare_repo(&dot_git) {
+            return Some(Self::DotGit);
+        }
+
+        if path.extension().is_some_and(|e| e == "git") && is_bare_repo_dir(path) {
+            return Some(Self::Sibling);
+        }
+
+        None
+    }
+
+    /// the path of the tree node for a repo called `name` cloned into `parent`.
+    pub fn repo_path(&self, parent: &Path, name: &str) -> PathBuf {
+        match self {
+            Self::DotBare | Self::DotGit => parent.join(name),
+            Self::Sibling => parent.join(format!("{}.git", name)),
+        }
+    }
+
+    /// the bare git directory of the repo at `repo`.
+    pub fn bare_dir(&self, repo: &Path) -> PathBuf {
+        match self {
+            Self::DotBare => repo.join(".bare"),
+            Self::DotGit => repo.join(".git"),
+            Self::Sibling => repo.to_path_buf(),
+        }
+    }
+
+    /// the directory new worktrees of the repo at `repo` are created in.
+    pub fn worktree_root(&self, repo: &Path, worktree_subdir: Option<&str>) -> PathBuf {
+        let root = match self {
+            Self::DotBare | Self::DotGit => repo.to_path_buf(),
+            Self::Sibling => repo.with_extension(""),
+        };
+        match worktree_subdir {
+            Some(subdir) if !subdir.is_empty() => root.join(subdir),
+            _ => root,
+        }
+    }
+
+    /// the directories the repo at `repo` keeps its git directory and worktrees in, with the git
+    /// directory last.
+    pub fn repo_dirs(&self, repo: &Path) -> Vec<PathBuf> {
+        match self {
+            Self::DotBare | Self::DotGit => vec![repo.to_path_buf()],
+            Self::Sibling => vec![repo.with_extension(""), repo.to_path_buf()],
+        }
+    }
+
+    /// the name shown in the project tree for the repo at `repo`.
+    pub fn display_name(&self, repo: &Path) -> String {
+        let name = match self {
+            Self::DotBare | Self::DotGit => repo.file_name(),
+            Self::Sibling => repo.file_stem(),
+        };
+        name.unwrap().to_string_lossy().into_owned()
+    }
+}
+
+/// every directory deleted along with the repo at `repo`: the linked worktrees added outside the
+/// directories its layout keeps it in, which would otherwise be left pointing at a git directory
+/// that no longer exists, then those directories, with the git directory last.
+pub fn repo_delete_dirs(repo: &Path) -> Vec<PathBuf> {
+    let repo_dirs = match RepoLayout::detect(repo) {
+        Some(layout) => layout.repo_dirs(repo),
+        None => vec![repo.to_path_buf()],
+    };
+
+    let mut worktrees: Vec<PathBuf> = git::linked_worktrees(repo)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|w| w.path)
+        .filter(|p| !repo_dirs.iter().any(|d| p.starts_with(d)))
+        .collect();
+    // a worktree inside another one goes along with it.
+    worktrees.sort();
+    let mut dirs: Vec<PathBuf> = vec![];
+    for worktree in worktrees {
+        if !dirs.iter().any(|d| worktree.starts_with(d)) {
+            dirs.push(worktree);
+        }
+    }
+    dirs.extend(repo_dirs.into_iter().filter(|d| d.is_dir()));
+    dirs
+}
+
+/// returns true if `path` looks like the inside of a git directory.
+pub fn is_bare_repo_dir(path: &Path) -> bool {
+    path.join("HEAD").is_file() && path.join("objects").is_dir() && path.join("refs").is_dir()
+}
+
+/// asks git whether the git directory at `git_dir` is configured as bare.
+fn is_bare_repo(git_dir: &Path) -> bool {
+    let Ok(output) = Command::new("git")
+        .arg("--git-dir")
+        .arg(git_dir)
+        .arg("config")
+        .arg("--bool")
+        .arg("core.bare")
+        .output()
+    else {
+        return false;
+    };
+    output.status.success() && String::from_utf8_lossy(&output.stdout).trim() == "true"
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::testing::{TempDir, git};
+
+    /// a bare clone of a repo with one commit at `git_dir`, with `repo` as the tree node and a
+    /// worktree added at `<root>/inside` and another outside the repo, then deletes it.
+    fn delete_repo_with_worktrees(tmp: &TempDir, repo: &Path, git_dir: &Path, root: &Path) {
+        let source = tmp.0.join("source");
+        fs::create_dir(&source).unwrap();
+        git(&source, &["init", "-q"]);
+        git(&source, &["commit", "-q", "--allow-empty", "-m", "first"]);
+        git(
+            &tmp.0,
+            &["clone", "-q", "--bare", "source", git_dir.to_str().unwrap()],
+        );
+        if git_dir.ends_with(".bare") {
+            fs::write(repo.join(".git"), "gitdir: ./.bare\n").unwrap();
+        }
+        let inside = root.join("inside");
+        let outside = tmp.0.join("outside");
+        for (branch, path) in [("inside", &inside), ("outside", &outside)] {
+            git(
+                git_dir,
+                &[
+                    "worktree",
+                    "add",
+                    "-q",
+                    "-b",
+                    branch,
+                    path.to_str().unwrap(),
+                ],
+            );
+        }
+
+        let dirs = repo_delete_dirs(repo);
+        assert_eq!(dirs.last().unwrap(), repo);
+        for dir in dirs {
+            fs::remove_dir_all(dir).unwrap();
+        }
+        let mut left: Vec<_> = fs::read_dir(&tmp.0)
+            .unwrap()
+            .map(|e| e.unwrap().file_name())
+            .collect();
+        left.sort();
+        assert_eq!(left, ["source"]);
+    }
+
+    #[test]
+    fn deleting_a_dot_bare_repo_removes_its_worktrees() {
+        let tmp = TempDir::new();
+        let repo = tmp.0.join("repo");
+        delete_repo_with_worktrees(&tmp, &repo, &repo.join(".bare"), &repo);
+    }
+
+    #[test]
+    fn deleting_a_dot_git_repo_removes_its_worktrees() {
+        let tmp = TempDir::new();
+        let repo = tmp.0.join("repo");
+        delete_repo_with_worktrees(&tmp, &repo, &repo.join(".git"), &repo);
+    }
+
+    #[test]
+    fn deleting_a_sibling_repo_removes_its_worktree_directory() {
+        let tmp = TempDir::new();
+        let repo = tmp.0.join("repo.git");
+        delete_repo_with_worktrees(&tmp, &repo, &repo, &tmp.0.join("repo"));
+    }
+}
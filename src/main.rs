@@ -1,34 +1,62 @@
 mod app;
+mod askpass;
+mod cache;
+mod cli;
 mod config;
+mod convert;
+mod exec;
+mod git;
+mod git_url;
+mod history;
+mod jobs;
+mod layout;
 mod multi_input;
+mod naming;
+mod ops;
 mod switch_screen;
 mod project_item;
+mod remotes;
+mod rewrite;
 mod screen;
+mod session;
+mod summary;
+mod task_view;
+#[cfg(test)]
+mod testing;
+mod transaction;
+mod watch;
 
-use std::io;
+use std::{env, io, path::Path, process::ExitCode};
 
 use app::App;
 use config::Config;
 
-fn main() -> io::Result<()> {
+fn main() -> io::Result<ExitCode> {
+    // git and ssh run gpm with the prompt as its only argument when they need a credential.
+    if let Some(socket) = env::var_os(askpass::SOCKET_VAR) {
+        let prompt = env::args().nth(1).unwrap_or_default();
+        return Ok(askpass::ask(Path::new(&socket), &prompt));
+    }
+    let config: Config = confy::load("gpm", "config").expect("could not load config.");
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    let dry_run = args.iter().any(|a| a == "--dry-run");
+    args.retain(|a| a != "--dry-run");
+    if !args.is_empty() {
+        return Ok(cli::run(&config, &args, dry_run));
+    }
+
     let mut terminal = ratatui::init();
 
     let mut app = App::default();
-    let config: Config = confy::load("gpm", "config").expect("could not load config.");
-    let mut forest = config.to_forest();
-    app.project_tree = forest;
-    let mut app_result = app.run(&mut terminal);
-    while *(app_result.as_ref().unwrap_or(&false)) {
-        app = App::default();
-        forest = config.to_forest();
-        app.project_tree = forest;
-        app_result = app.run(&mut terminal);
-    }
+    app.project_tree = config.to_forest();
+    app.restore_session(session::load());
+    app.start_watching();
+    app.config = config;
+    app.dry_run = dry_run;
+    app.askpass = askpass::listen().ok();
+    let app_result = app.run(&mut terminal);
 
     ratatui::restore();
 
-    match app_result {
-        Ok(_) => Ok(()),
-        Err(e) => Err(e),
-    }
+    app_result.map(|_| ExitCode::SUCCESS)
 }
@@ -16,6 +16,8 @@ pub struct MultiInputState {
 struct InputBox {
     prompt: String,
     handler: Input,
+    /// whether the content is hidden as it is typed, e.g. for a password.
+    secret: bool,
 }
 
 impl InputBox {
@@ -23,17 +25,18 @@ impl InputBox {
         Self {
             prompt,
             handler: Input::new("".to_string()),
+            secret: false,
         }
     }
 }
 
 impl MultiInputState {
     pub fn new(title: String, input_prompts: Vec<String>) -> Self {
-        return MultiInputState {
+        MultiInputState {
             title,
             boxes: input_prompts.into_iter().map(InputBox::new).collect(),
             idx: 0,
-        };
+        }
     }
 
     pub fn next_box(&mut self) {
@@ -49,27 +52,40 @@ impl MultiInputState {
         match e {
             Event::Key(k) => match k.code {
                 KeyCode::Esc => {
-                    return true;
+                    true
                 }
                 KeyCode::Enter => {
-                    return true;
+                    true
                 }
                 KeyCode::Tab => {
                     self.next_box();
-                    return false;
+                    false
                 },
                 _ => {
                     self.boxes[self.idx].handler.handle_event(e);
                     false
                 }
             },
-            _ => return false,
+            _ => false,
         }
     }
 
     pub fn get_content_at(&self, idx: usize) -> String {
         self.boxes[idx].handler.value().to_string()
     }
+
+    pub fn set_content_at(&mut self, idx: usize, value: String) {
+        self.boxes[idx].handler = Input::new(value);
+    }
+
+    /// hides the content of the box at `idx` as it is typed.
+    pub fn set_secret_at(&mut self, idx: usize) {
+        self.boxes[idx].secret = true;
+    }
+
+    pub fn get_box_count(&self) -> usize {
+        self.boxes.len()
+    }
 }
 
 impl StatefulWidget for MultiInput {
@@ -85,16 +101,18 @@ impl StatefulWidget for MultiInput {
             .direction(Direction::Horizontal)
             .constraints(vec![
                 Constraint::Length(3),
-                Constraint::Length(area.width - 6),
+                Constraint::Length(area.width.saturating_sub(6)),
                 Constraint::Length(3),
             ])
             .split(area);
         let used_height = 3 * state.boxes.len() as u16;
-        let mut vert_constraints = vec![Constraint::Length((area.height - used_height) / 2)];
+        let mut vert_constraints = vec![Constraint::Length(
+            area.height.saturating_sub(used_height) / 2,
+        )];
         for _ in 0..state.boxes.len() {
             vert_constraints.push(Constraint::Length(3))
         }
-        vert_constraints.push(Constraint::Length((area.height - used_height) / 2));
+        vert_constraints.push(Constraint::Length(area.height.saturating_sub(used_height) / 2));
 
         let input_layouts = Layout::default()
             .direction(Direction::Vertical)
@@ -110,8 +128,13 @@ impl StatefulWidget for MultiInput {
 
         for (i, b) in state.boxes.iter().enumerate() {
             let scroll = b.handler.visual_scroll(width as usize);
-            let mut widget = Paragraph::new(b.handler.value()).scroll((0, scroll as u16));
-            if i == state.idx as usize {
+            let value = if b.secret {
+                "*".repeat(b.handler.value().chars().count())
+            } else {
+                b.handler.value().to_string()
+            };
+            let mut widget = Paragraph::new(value).scroll((0, scroll as u16));
+            if i == state.idx {
                 widget = widget.style(Style::new().yellow());
             }
             widget = widget.block(Block::bordered().title(state.boxes[i].prompt.to_string()));
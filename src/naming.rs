@@ -0,0 +1,187 @@
+use std::{
+    path::Path,
+    process::Command,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// the values substituted into branch and directory name templates.
+#[derive(Debug, Clone, Default)]
+pub struct TemplateVars {
+    /// `{user}`, the kebab-cased git user name.
+    pub user: String,
+    /// `{date}`, today's date as `YYYY-MM-DD`.
+    pub date: String,
+    /// `{ticket}`, the ticket id as typed.
+    pub ticket: Option<String>,
+    /// `{slug}`, free text that is kebab-cased before substitution.
+    pub slug: Option<String>,
+}
+
+impl TemplateVars {
+    /// the variables known before the user has filled in the form, for the repo at `repo`.
+    pub fn for_repo(repo: &Path) -> Self {
+        Self {
+            user: git_user(repo),
+            date: today(),
+            ticket: None,
+            slug: None,
+        }
+    }
+}
+
+/// substitutes the variables in `template`. variables without a value are left in place, so a
+/// partially rendered template can be used to pre-fill a form.
+pub fn render(template: &str, vars: &TemplateVars) -> String {
+    let mut out = template
+        .replace("{user}", &vars.user)
+        .replace("{date}", &vars.date);
+    if let Some(ref ticket) = vars.ticket {
+        out = out.replace("{ticket}", ticket.trim());
+    }
+    if let Some(ref slug) = vars.slug {
+        out = out.replace("{slug}", &kebab_case(slug));
+    }
+    out
+}
+
+/// lowercases `s` and joins its alphanumeric runs with dashes.
+pub fn kebab_case(s: &str) -> String {
+    s.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(|w| w.to_lowercase())
+        .collect::<Vec<String>>()
+        .join("-")
+}
+
+/// checks `branch` is a valid branch name with `git check-ref-format --branch`.
+pub fn check_branch_name(branch: &str) -> Result<(), String> {
+    let output = Command::new("git")
+        .arg("check-ref-format")
+        .arg("--branch")
+        .arg(branch)
+        .output()
+        .map_err(|e| format!("Failed to start 'git check-ref-format' process: {}", e))?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(format!("'{}' is not a valid branch name.", branch))
+    }
+}
+
+fn git_user(repo: &Path) -> String {
+    let Ok(output) = Command::new("git")
+        .current_dir(repo)
+        .arg("config")
+        .arg("user.name")
+        .output()
+    else {
+        return String::new();
+    };
+    kebab_case(&String::from_utf8_lossy(&output.stdout))
+}
+
+fn today() -> String {
+    let (y, m, d) = civil_from_days(local_secs(now()).div_euclid(86400));
+    format!("{:04}-{:02}-{:02}", y, m, d)
+}
+
+/// the current time in seconds since the unix epoch.
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// formats `secs` since the unix epoch as `YYYY-MM-DD HH:MM:SS` in local time.
+pub fn format_timestamp(secs: u64) -> String {
+    let local = local_secs(secs);
+    let (y, m, d) = civil_from_days(local.div_euclid(86400));
+    let time = local.rem_euclid(86400);
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        y,
+        m,
+        d,
+        time / 3600,
+        time / 60 % 60,
+        time % 60
+    )
+}
+
+/// shifts `secs` since the unix epoch by the offset of the local time zone at that moment, so
+/// that it can be split into a local date and time.
+fn local_secs(secs: u64) -> i64 {
+    let secs = secs as i64;
+    let time = secs as libc::time_t;
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    // the offset is left at zero, i.e. UTC, if the local time cannot be worked out.
+    unsafe { libc::localtime_r(&time, &mut tm) };
+    secs + tm.tm_gmtoff
+}
+
+/// converts days since the unix epoch to a (year, month, day) date in the proleptic gregorian
+/// calendar, using Howard Hinnant's `civil_from_days` algorithm.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let y = yoe + era * 400 + if m <= 2 { 1 } else { 0 };
+    (y, m, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars() -> TemplateVars {
+        TemplateVars {
+            user: "jane-doe".to_string(),
+            date: "2024-02-29".to_string(),
+            ticket: None,
+            slug: None,
+        }
+    }
+
+    #[test]
+    fn renders_known_variables() {
+        let vars = TemplateVars {
+            ticket: Some(" ABC-123 ".to_string()),
+            slug: Some("Fix the Login page!".to_string()),
+            ..vars()
+        };
+        assert_eq!(
+            render("{user}/{ticket}-{slug}", &vars),
+            "jane-doe/ABC-123-fix-the-login-page"
+        );
+        assert_eq!(render("wip-{date}", &vars), "wip-2024-02-29");
+    }
+
+    #[test]
+    fn leaves_unset_variables_in_place() {
+        assert_eq!(
+            render("{user}/{ticket}-{slug}", &vars()),
+            "jane-doe/{ticket}-{slug}"
+        );
+    }
+
+    #[test]
+    fn leaves_unknown_variables_in_place() {
+        assert_eq!(render("{branch}/{user}", &vars()), "{branch}/jane-doe");
+    }
+
+    #[test]
+    fn converts_days_to_dates() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+        assert_eq!(civil_from_days(11016), (2000, 2, 29));
+        assert_eq!(civil_from_days(19782), (2024, 2, 29));
+        assert_eq!(civil_from_days(19783), (2024, 3, 1));
+        assert_eq!(civil_from_days(-25508), (1900, 3, 1));
+    }
+}
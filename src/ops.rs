@@ -0,0 +1,884 @@
+use std::{
+    ffi::OsString,
+    fs::{self, File},
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Output},
+    str::from_utf8,
+    sync::{Arc, Mutex, PoisonError},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cache,
+    exec::{self, CommandExt},
+    git,
+    layout::RepoLayout,
+    transaction::{Step, Transaction},
+};
+
+const FETCH_REFSPEC: &str = "+refs/heads/*:refs/remotes/origin/*";
+
+/// the contents of the `.git` file of a repo using the `.bare` layout.
+const GITDIR_FILE_CONTENTS: &str = "gitdir: ./.bare";
+
+/// options for cutting down how much is fetched and checked out by a clone.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct CloneOptions {
+    /// a partial clone filter, e.g. `blob:none`.
+    pub filter: Option<String>,
+    /// only fetch this many commits of history.
+    pub depth: Option<u32>,
+    /// only fetch the remote's default branch.
+    pub single_branch: bool,
+    /// directories to check out, as cone mode sparse-checkout patterns. used for ordinary clones
+    /// and new worktrees, as a bare clone has nothing checked out. empty checks out everything.
+    pub sparse_checkout: Vec<String>,
+    /// borrow objects from a mirror of the remote kept in the gpm cache directory, so repeated
+    /// clones of the same repo only download it once. the mirror is created or updated first.
+    pub reference_cache: bool,
+    /// check out lfs pointer files instead of downloading lfs objects one at a time during the
+    /// checkout, then fetch them all in one go afterwards.
+    pub lfs_skip_smudge: bool,
+}
+
+impl CloneOptions {
+    /// the arguments passed to `git clone` for these options.
+    fn clone_args(&self) -> Vec<String> {
+        let mut args = vec![];
+        if let Some(ref filter) = self.filter {
+            args.push(format!("--filter={}", filter));
+        }
+        if let Some(depth) = self.depth {
+            args.push(format!("--depth={}", depth));
+        }
+        if self.single_branch {
+            args.push("--single-branch".to_string());
+        }
+        args
+    }
+
+    /// the environment for git commands that check files out, which stops git lfs downloading
+    /// objects if smudging is skipped.
+    pub fn lfs_env(&self) -> Option<(&'static str, &'static str)> {
+        self.lfs_skip_smudge.then_some(("GIT_LFS_SKIP_SMUDGE", "1"))
+    }
+
+    /// the `--reference` arguments for `git clone` if the clone cache is used.
+    fn reference_args(&self, link: &str) -> Result<Vec<OsString>, String> {
+        if !self.reference_cache {
+            return Ok(vec![]);
+        }
+        let mirror = cache::mirror_path(link)?;
+        Ok(vec![OsString::from("--reference"), mirror.into_os_string()])
+    }
+}
+
+/// a single change made to a repo or worktree.
+#[derive(Debug, Clone)]
+pub enum RepoStep {
+    /// creates `path` and any missing parents. `created` records the directories the latest run
+    /// created, which are the only ones removed on rollback.
+    CreateDir {
+        path: PathBuf,
+        created: Arc<Mutex<Vec<PathBuf>>>,
+    },
+    /// creates or updates the clone cache mirror of `link`.
+    UpdateMirror { link: String },
+    /// runs `git clone` in `cwd`, creating `target`, which is removed on rollback.
+    Clone {
+        cwd: PathBuf,
+        args: Vec<OsString>,
+        target: PathBuf,
+        env: Option<(&'static str, &'static str)>,
+    },
+    /// creates an empty bare repo at `git_dir`, which is removed on rollback.
+    InitBare { cwd: PathBuf, git_dir: PathBuf },
+    /// writes the `.git` file pointing git at the `.bare` directory of a worktree repo.
+    WriteGitdirFile(PathBuf),
+    /// gives a bare clone a fetch refspec, which it is created without, so that new worktrees get
+    /// remote tracking branches. a single branch clone only tracks the branch that was cloned.
+    SetFetchRefspec {
+        git_dir: PathBuf,
+        single_branch: bool,
+    },
+    /// runs git in `dir`. anything it changes inside a repo created earlier in the transaction is
+    /// removed with it, so there is nothing to undo.
+    Git {
+        dir: PathBuf,
+        args: Vec<String>,
+        env: Option<(&'static str, &'static str)>,
+    },
+    /// adds a worktree at `path` on the new branch `branch`. the worktree and branch are removed
+    /// on rollback.
+    AddWorktree {
+        git_dir: PathBuf,
+        path: PathBuf,
+        branch: String,
+        start_args: Vec<String>,
+        no_checkout: bool,
+        env: Option<(&'static str, &'static str)>,
+    },
+    /// downloads the lfs objects of a worktree that uses lfs.
+    FetchLfs(PathBuf),
+    /// points a remote of `repo` at `new_url`, or sets its push url. the old url is put back on
+    /// rollback.
+    SetRemoteUrl {
+        repo: PathBuf,
+        remote: String,
+        push: bool,
+        old_url: String,
+        new_url: String,
+    },
+    /// removes a file, which is written back with its `previous` contents on rollback.
+    RemoveFile { path: PathBuf, previous: String },
+    /// deletes a directory and everything in it. this cannot be rolled back.
+    RemoveDir(PathBuf),
+    /// removes each directory in turn while it is empty, stopping at the first one that is not.
+    RemoveEmptyDirs(Vec<PathBuf>),
+}
+
+impl RepoStep {
+    pub fn git(dir: &Path, args: &[&str]) -> Self {
+        Self::Git {
+            dir: dir.to_path_buf(),
+            args: args.iter().map(|a| a.to_string()).collect(),
+            env: None,
+        }
+    }
+
+    /// the command `apply` runs, for the steps that run a single command.
+    fn command(&self) -> Option<Command> {
+        let mut cmd = Command::new("git");
+        match self {
+            Self::Clone { cwd, args, env, .. } => {
+                cmd.current_dir(cwd)
+                    .arg("clone")
+                    .args(exec::progress_flag())
+                    .args(args)
+                    .envs(*env);
+            }
+            Self::InitBare { cwd, git_dir } => {
+                cmd.current_dir(cwd).args(["init", "--bare"]).arg(git_dir);
+            }
+            Self::Git { dir, args, env } => {
+                // fetches report their progress like clones do.
+                let progress = (args.first().map(String::as_str) == Some("fetch"))
+                    .then(exec::progress_flag)
+                    .flatten();
+                cmd.current_dir(dir)
+                    .args(args.iter().take(1))
+                    .args(progress)
+                    .args(args.iter().skip(1))
+                    .envs(*env);
+            }
+            Self::AddWorktree {
+                git_dir,
+                path,
+                branch,
+                start_args,
+                no_checkout,
+                env,
+            } => {
+                cmd.current_dir(git_dir)
+                    .args(["worktree", "add"])
+                    .args(no_checkout.then_some("--no-checkout"))
+                    .arg("-b")
+                    .arg(branch)
+                    .arg(path)
+                    .args(start_args)
+                    .envs(*env);
+            }
+            Self::FetchLfs(worktree) => {
+                cmd.current_dir(worktree).args(["lfs", "pull"]);
+            }
+            Self::SetRemoteUrl {
+                repo,
+                remote,
+                push,
+                new_url,
+                ..
+            } => {
+                cmd.current_dir(repo)
+                    .args(["remote", "set-url"])
+                    .args(push.then_some("--push"))
+                    .args([remote, new_url]);
+            }
+            Self::CreateDir { .. }
+            | Self::UpdateMirror { .. }
+            | Self::WriteGitdirFile(_)
+            | Self::SetFetchRefspec { .. }
+            | Self::RemoveFile { .. }
+            | Self::RemoveDir(_)
+            | Self::RemoveEmptyDirs(_) => return None,
+        }
+        Some(cmd)
+    }
+}
+
+impl Step for RepoStep {
+    fn describe(&self) -> String {
+        match self {
+            Self::CreateDir { path, .. } => format!("create directory {:?}", path),
+            Self::UpdateMirror { link } => format!("update the clone cache mirror of {}", link),
+            Self::Clone { args, .. } => format!(
+                "git clone {}",
+                args.iter()
+                    .map(|a| a.to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            ),
+            Self::InitBare { git_dir, .. } => format!("git init --bare {:?}", git_dir),
+            Self::WriteGitdirFile(repo_path) => format!("write {:?}", repo_path.join(".git")),
+            Self::SetFetchRefspec { .. } => "git config remote.origin.fetch".to_string(),
+            Self::Git { dir, args, .. } => format!("git {} in {:?}", args.join(" "), dir),
+            Self::AddWorktree { path, branch, .. } => {
+                format!("git worktree add -b {} {:?}", branch, path)
+            }
+            Self::FetchLfs(worktree) => format!("git lfs pull in {:?}", worktree),
+            Self::SetRemoteUrl {
+                repo,
+                remote,
+                push,
+                old_url,
+                new_url,
+            } => format!(
+                "{:?} {}{}: {} -> {}",
+                repo,
+                remote,
+                if *push { " (push)" } else { "" },
+                old_url,
+                new_url
+            ),
+            Self::RemoveFile { path, .. } => format!("remove {:?}", path),
+            Self::RemoveDir(path) => format!("delete {:?}", path),
+            Self::RemoveEmptyDirs(dirs) => format!("remove {} empty directories", dirs.len()),
+        }
+    }
+
+    fn plan(&self) -> Vec<String> {
+        if let Some(cmd) = self.command() {
+            return vec![exec::display_command(&cmd)];
+        }
+        let quote = |p: &Path| exec::shell_quote(p.as_os_str());
+        match self {
+            Self::CreateDir { path, .. } => missing_dirs(path)
+                .iter()
+                .map(|d| format!("mkdir {}", quote(d)))
+                .collect(),
+            Self::UpdateMirror { link } => match cache::mirror_path(link) {
+                Ok(mirror) => vec![exec::display_command(&cache::mirror_command(link, &mirror))],
+                Err(e) => vec![e],
+            },
+            Self::WriteGitdirFile(repo_path) => vec![format!(
+                "write {}: {:?}",
+                quote(&repo_path.join(".git")),
+                GITDIR_FILE_CONTENTS
+            )],
+            Self::SetFetchRefspec {
+                git_dir,
+                single_branch,
+            } => {
+                // the branch a single branch clone tracks is only known once it has been cloned.
+                let refspec = if *single_branch {
+                    "+refs/heads/<cloned branch>:refs/remotes/origin/<cloned branch>"
+                } else {
+                    FETCH_REFSPEC
+                };
+                vec![exec::display_command(&refspec_command(git_dir, refspec))]
+            }
+            Self::RemoveFile { path, .. } => vec![format!("rm {}", quote(path))],
+            Self::RemoveDir(path) => vec![format!("rm -rf {}", quote(path))],
+            Self::RemoveEmptyDirs(dirs) => {
+                dirs.iter().map(|d| format!("rmdir {}", quote(d))).collect()
+            }
+            _ => vec![],
+        }
+    }
+
+    fn apply(&self) -> Result<(), String> {
+        if let Self::FetchLfs(worktree) = self
+            && !git::uses_lfs(worktree)
+        {
+            return Ok(());
+        }
+        if let Some(mut cmd) = self.command() {
+            return run_step(&mut cmd);
+        }
+        match self {
+            Self::CreateDir { path, created } => {
+                let mut created = created.lock().unwrap_or_else(PoisonError::into_inner);
+                created.clear();
+                // each directory is created on its own, failing if it has appeared since, so that
+                // one created by another job is never removed on rollback.
+                for dir in missing_dirs(path) {
+                    if let Err(e) = fs::create_dir(&dir) {
+                        let _ = remove_created(&created);
+                        return Err(format!("Failed to create {:?}: {}", dir, e));
+                    }
+                    created.push(dir);
+                }
+                Ok(())
+            }
+            Self::UpdateMirror { link } => cache::update_mirror(link)
+                .map(|_| ())
+                .map_err(|summary| summary.join("\n")),
+            Self::WriteGitdirFile(repo_path) => {
+                write_gitdir_file(repo_path).map_err(|summary| summary.join("\n"))
+            }
+            Self::SetFetchRefspec {
+                git_dir,
+                single_branch,
+            } => {
+                let refspec = if *single_branch {
+                    let branch = git_stdout(git_dir, &["symbolic-ref", "--short", "HEAD"])?;
+                    format!("+refs/heads/{0}:refs/remotes/origin/{0}", branch)
+                } else {
+                    FETCH_REFSPEC.to_string()
+                };
+                run_step(&mut refspec_command(git_dir, &refspec))
+            }
+            Self::RemoveFile { path, .. } => fs::remove_file(path).map_err(|e| e.to_string()),
+            Self::RemoveDir(path) => fs::remove_dir_all(path).map_err(|e| e.to_string()),
+            Self::RemoveEmptyDirs(dirs) => {
+                for dir in dirs.iter() {
+                    if fs::remove_dir(dir).is_err() {
+                        break;
+                    }
+                }
+                Ok(())
+            }
+            // the rest run their command above.
+            _ => Ok(()),
+        }
+    }
+
+    fn undo(&self) -> Result<(), String> {
+        match self {
+            Self::CreateDir { created, .. } => {
+                remove_created(&created.lock().unwrap_or_else(PoisonError::into_inner))
+            }
+            Self::Clone { target, .. } => {
+                // git removes a clone it did not finish itself.
+                if !target.exists() {
+                    return Ok(());
+                }
+                fs::remove_dir_all(target).map_err(|e| e.to_string())
+            }
+            Self::InitBare { git_dir, .. } => {
+                fs::remove_dir_all(git_dir).map_err(|e| e.to_string())
+            }
+            Self::WriteGitdirFile(repo_path) => {
+                fs::remove_file(repo_path.join(".git")).map_err(|e| e.to_string())
+            }
+            Self::AddWorktree {
+                git_dir,
+                path,
+                branch,
+                ..
+            } => {
+                run_step(
+                    Command::new("git")
+                        .current_dir(git_dir)
+                        .args(["worktree", "remove", "--force"])
+                        .arg(path),
+                )?;
+                run_step(
+                    Command::new("git")
+                        .current_dir(git_dir)
+                        .args(["branch", "-D", branch]),
+                )
+            }
+            Self::SetRemoteUrl {
+                repo,
+                remote,
+                push,
+                old_url,
+                ..
+            } => run_step(
+                Command::new("git")
+                    .current_dir(repo)
+                    .args(["remote", "set-url"])
+                    .args(push.then_some("--push"))
+                    .args([remote, old_url]),
+            ),
+            Self::RemoveFile { path, previous } => {
+                fs::write(path, previous).map_err(|e| e.to_string())
+            }
+            Self::RemoveDir(_) => Err("a deleted directory cannot be restored".to_string()),
+            Self::UpdateMirror { .. }
+            | Self::SetFetchRefspec { .. }
+            | Self::Git { .. }
+            | Self::FetchLfs(_)
+            | Self::RemoveEmptyDirs(_) => Ok(()),
+        }
+    }
+
+    fn keys(&self) -> Vec<PathBuf> {
+        match self {
+            Self::CreateDir { path, .. } => vec![path.clone()],
+            Self::UpdateMirror { link } => cache::mirror_path(link).into_iter().collect(),
+            Self::Clone { target, .. } => vec![target.clone()],
+            Self::InitBare { git_dir, .. } | Self::SetFetchRefspec { git_dir, .. } => {
+                vec![git_dir.clone()]
+            }
+            Self::WriteGitdirFile(repo) | Self::SetRemoteUrl { repo, .. } => vec![repo.clone()],
+            Self::Git { dir, .. } => vec![dir.clone()],
+            Self::AddWorktree { git_dir, path, .. } => vec![git_dir.clone(), path.clone()],
+            Self::FetchLfs(worktree) => vec![worktree.clone()],
+            Self::RemoveFile { path, .. } | Self::RemoveDir(path) => vec![path.clone()],
+            Self::RemoveEmptyDirs(dirs) => dirs.clone(),
+        }
+    }
+}
+
+/// the command setting the fetch refspec of the origin remote of `git_dir`.
+fn refspec_command(git_dir: &Path, refspec: &str) -> Command {
+    let mut cmd = Command::new("git");
+    cmd.current_dir(git_dir)
+        .args(["config", "remote.origin.fetch", refspec]);
+    cmd
+}
+
+/// runs a step's command, failing with its error output if it fails.
+fn run_step(cmd: &mut Command) -> Result<(), String> {
+    let output = cmd
+        .run()
+        .map_err(|e| format!("Failed to start 'git' process: {}", e))?;
+    if output.status.success() {
+        return Ok(());
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+    if stderr.is_empty() {
+        Err(format!("exited with {}", output.status))
+    } else {
+        Err(stderr)
+    }
+}
+
+fn git_stdout(dir: &Path, args: &[&str]) -> Result<String, String> {
+    let output = Command::new("git")
+        .current_dir(dir)
+        .args(args)
+        .run()
+        .map_err(|e| format!("Failed to start 'git' process: {}", e))?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// the step creating `path` and any of its parents that do not exist yet, if any are missing.
+fn create_dir_step(path: &Path) -> Option<RepoStep> {
+    if path.exists() {
+        return None;
+    }
+    Some(RepoStep::CreateDir {
+        path: path.to_path_buf(),
+        created: Arc::default(),
+    })
+}
+
+/// `path` and those of its parents that do not exist, outermost first.
+fn missing_dirs(path: &Path) -> Vec<PathBuf> {
+    let mut missing: Vec<PathBuf> = path
+        .ancestors()
+        .take_while(|p| !p.exists())
+        .map(Path::to_path_buf)
+        .collect();
+    missing.reverse();
+    missing
+}
+
+/// removes the directories in `created`, innermost first. they are only removed while empty, so
+/// anything another job has put in them since is kept, along with the directories holding it.
+fn remove_created(created: &[PathBuf]) -> Result<(), String> {
+    for dir in created.iter().rev() {
+        fs::remove_dir(dir).map_err(|e| format!("Failed to remove {:?}: {}", dir, e))?;
+    }
+    Ok(())
+}
+
+/// plans cloning `link` as a worktree repo called `name` inside `parent`, arranged according to
+/// `layout`.
+pub fn plan_worktree_clone(
+    layout: RepoLayout,
+    parent: &Path,
+    name: &str,
+    link: &str,
+    options: &CloneOptions,
+) -> Result<Transaction<RepoStep>, String> {
+    let repo_path = layout.repo_path(parent, name);
+    let bare_dir = layout.bare_dir(&repo_path);
+    if repo_path.exists() || bare_dir.exists() {
+        return Err(format!("{:?} already exists.", repo_path));
+    }
+
+    let mut steps = vec![];
+    // a sibling layout repo has no directory of its own until its first worktree is added.
+    let dir = if layout == RepoLayout::Sibling {
+        parent
+    } else {
+        &repo_path
+    };
+    steps.extend(create_dir_step(dir));
+    if options.reference_cache {
+        steps.push(RepoStep::UpdateMirror {
+            link: link.to_string(),
+        });
+    }
+
+    let mut args = vec![OsString::from("--bare")];
+    args.extend(options.clone_args().into_iter().map(OsString::from));
+    args.extend(options.reference_args(link)?);
+    args.push(OsString::from(link));
+    args.push(bare_dir.clone().into_os_string());
+    steps.push(RepoStep::Clone {
+        cwd: parent.to_path_buf(),
+        args,
+        target: bare_dir.clone(),
+        env: None,
+    });
+
+    if layout == RepoLayout::DotBare {
+        steps.push(RepoStep::WriteGitdirFile(repo_path.clone()));
+    }
+    steps.push(RepoStep::SetFetchRefspec {
+        git_dir: bare_dir.clone(),
+        single_branch: options.single_branch,
+    });
+
+    let mut fetch = vec!["fetch".to_string()];
+    fetch.extend(options.depth.map(|d| format!("--depth={}", d)));
+    fetch.push("origin".to_string());
+    steps.push(RepoStep::Git {
+        dir: bare_dir,
+        args: fetch,
+        env: None,
+    });
+
+    Ok(Transaction::new(
+        format!("Clone {} into {:?}", link, repo_path),
+        repo_path,
+        steps,
+    ))
+}
+
+/// plans cloning `link` as an ordinary repo called `name` inside `parent`.
+pub fn plan_non_worktree_clone(
+    parent: &Path,
+    name: &str,
+    link: &str,
+    options: &CloneOptions,
+) -> Result<Transaction<RepoStep>, String> {
+    let repo_path = parent.join(name);
+    if repo_path.exists() {
+        return Err(format!("{:?} already exists.", repo_path));
+    }
+
+    let mut steps: Vec<RepoStep> = create_dir_step(parent).into_iter().collect();
+    if options.reference_cache {
+        steps.push(RepoStep::UpdateMirror {
+            link: link.to_string(),
+        });
+    }
+
+    let mut args: Vec<OsString> = options
+        .clone_args()
+        .into_iter()
+        .map(OsString::from)
+        .collect();
+    args.extend(options.reference_args(link)?);
+    if !options.sparse_checkout.is_empty() {
+        args.push(OsString::from("--sparse"));
+    }
+    args.push(OsString::from(link));
+    args.push(OsString::from(name));
+    steps.push(RepoStep::Clone {
+        cwd: parent.to_path_buf(),
+        args,
+        target: repo_path.clone(),
+        env: options.lfs_env(),
+    });
+
+    steps.extend(sparse_checkout_steps(
+        &repo_path,
+        &options.sparse_checkout,
+        options.lfs_env(),
+    ));
+    if options.lfs_skip_smudge {
+        steps.push(RepoStep::FetchLfs(repo_path.clone()));
+    }
+
+    Ok(Transaction::new(
+        format!("Clone {} into {:?}", link, repo_path),
+        repo_path,
+        steps,
+    ))
+}
+
+/// the steps restricting the worktree at `worktree` to the directories in `patterns` with a cone
+/// mode sparse-checkout, then checking out the matching files. none if there are no patterns.
+/// both steps check files out, so they run with the same lfs environment as the clone.
+fn sparse_checkout_steps(
+    worktree: &Path,
+    patterns: &[String],
+    env: Option<(&'static str, &'static str)>,
+) -> Vec<RepoStep> {
+    if patterns.is_empty() {
+        return vec![];
+    }
+    let mut set = vec!["sparse-checkout", "set", "--cone"];
+    set.extend(patterns.iter().map(|p| p.as_str()));
+    [set, vec!["read-tree", "-mu", "HEAD"]]
+        .into_iter()
+        .map(|args| RepoStep::Git {
+            dir: worktree.to_path_buf(),
+            args: args.into_iter().map(String::from).collect(),
+            env,
+        })
+        .collect()
+}
+
+/// the step checking out the submodules of the worktree at `worktree`.
+pub fn submodule_step(worktree: &Path) -> RepoStep {
+    RepoStep::git(worktree, &["submodule", "update", "--init", "--recursive"])
+}
+
+/// plans adding a worktree at `path` on a new branch `branch` to the repo whose git directory is
+/// `git_dir`, starting from `start_args`. a sparse worktree is added without a checkout, which
+/// the sparse-checkout then fills in with only the matching files.
+pub fn plan_worktree_add(
+    title: String,
+    git_dir: &Path,
+    path: &Path,
+    branch: &str,
+    start_args: Vec<String>,
+    sparse_patterns: &[String],
+    options: &CloneOptions,
+) -> Transaction<RepoStep> {
+    let mut steps = vec![RepoStep::AddWorktree {
+        git_dir: git_dir.to_path_buf(),
+        path: path.to_path_buf(),
+        branch: branch.to_string(),
+        start_args,
+        no_checkout: !sparse_patterns.is_empty(),
+        env: options.lfs_env(),
+    }];
+    steps.extend(sparse_checkout_steps(
+        path,
+        sparse_patterns,
+        options.lfs_env(),
+    ));
+    if options.lfs_skip_smudge {
+        steps.push(RepoStep::FetchLfs(path.to_path_buf()));
+    }
+    Transaction::new(title, path.to_path_buf(), steps)
+}
+
+/// plans cloning `fork_link` as a worktree repo with it as `origin`, and adding `upstream_link`
+/// as `upstream`. new branches are based on `upstream` and pushed to `origin`.
+pub fn plan_fork_clone(
+    layout: RepoLayout,
+    parent: &Path,
+    name: &str,
+    fork_link: &str,
+    upstream_link: &str,
+    options: &CloneOptions,
+) -> Result<Transaction<RepoStep>, String> {
+    let mut transaction = plan_worktree_clone(layout, parent, name, fork_link, options)?;
+    let bare_dir = layout.bare_dir(&layout.repo_path(parent, name));
+
+    for args in [
+        vec!["remote", "add", "upstream", upstream_link],
+        vec!["fetch", "upstream"],
+        vec!["remote", "set-head", "upstream", "--auto"],
+        vec!["config", "checkout.defaultRemote", "upstream"],
+        vec!["config", "remote.pushDefault", "origin"],
+    ] {
+        transaction.push(RepoStep::git(&bare_dir, &args));
+    }
+    Ok(transaction)
+}
+
+/// writes the `.git` file pointing git at the `.bare` directory of a worktree repo.
+pub fn write_gitdir_file(repo_path: &Path) -> Result<(), Vec<String>> {
+    let file_path = repo_path.join(".git");
+    let Ok(mut f) = File::create(&file_path) else {
+        return Err(vec![format!(
+            "Failed to create .git file at {:?}",
+            &file_path
+        )]);
+    };
+    if let Err(e) = f.write_all(GITDIR_FILE_CONTENTS.as_bytes()) {
+        return Err(vec![
+            format!("Failed to write to .git file at {:?}", &file_path),
+            format!("Error: {}", e),
+        ]);
+    };
+    Ok(())
+}
+
+/// the summary lines for a finished command, including its output if it failed.
+pub fn summarise_output(desc: &str, output: Output) -> Vec<String> {
+    if output.status.success() {
+        vec![format!("SUCCESS: {}", desc)]
+    } else {
+        vec![
+            format!("FAILURE: {}", desc),
+            format!(
+                "STDOUT: {}",
+                from_utf8(&output.stdout).unwrap_or("couldnt read stdout as utf-8")
+            ),
+            format!(
+                "STDERR: {}",
+                from_utf8(&output.stderr).unwrap_or("couldnt read stderr as utf-8")
+            ),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    fn options() -> CloneOptions {
+        CloneOptions {
+            filter: Some("blob:none".to_string()),
+            depth: Some(1),
+            single_branch: true,
+            sparse_checkout: vec!["src".to_string(), "docs/api".to_string()],
+            reference_cache: false,
+            lfs_skip_smudge: true,
+        }
+    }
+
+    /// the commands a dry run of `plan` lists, without the descriptions of the steps.
+    fn commands(plan: Result<Transaction<RepoStep>, String>) -> Vec<String> {
+        plan.unwrap()
+            .dry_run()
+            .iter()
+            .filter_map(|l| l.strip_prefix("   ").map(str::to_string))
+            .collect()
+    }
+
+    #[test]
+    fn clone_args_follow_the_options() {
+        assert_eq!(
+            options().clone_args(),
+            ["--filter=blob:none", "--depth=1", "--single-branch"]
+        );
+        assert!(CloneOptions::default().clone_args().is_empty());
+    }
+
+    #[test]
+    fn lfs_env_is_only_set_when_skipping_smudge() {
+        assert_eq!(options().lfs_env(), Some(("GIT_LFS_SKIP_SMUDGE", "1")));
+        assert_eq!(CloneOptions::default().lfs_env(), None);
+    }
+
+    #[test]
+    fn sparse_lfs_clone_checks_out_without_lfs_then_fetches_it() {
+        let parent = Path::new("/");
+        let plan = plan_non_worktree_clone(parent, "gpm-test-repo", "/srv/repo.git", &options());
+        assert_eq!(
+            commands(plan),
+            [
+                "cd / && GIT_LFS_SKIP_SMUDGE=1 git clone --filter=blob:none --depth=1 \
+                 --single-branch --sparse /srv/repo.git gpm-test-repo",
+                "cd /gpm-test-repo && GIT_LFS_SKIP_SMUDGE=1 git sparse-checkout set --cone src \
+                 docs/api",
+                "cd /gpm-test-repo && GIT_LFS_SKIP_SMUDGE=1 git read-tree -mu HEAD",
+                "cd /gpm-test-repo && git lfs pull",
+            ]
+        );
+    }
+
+    #[test]
+    fn plain_clone_has_no_sparse_or_lfs_steps() {
+        let parent = Path::new("/");
+        let plan = plan_non_worktree_clone(
+            parent,
+            "gpm-test-repo",
+            "/srv/repo.git",
+            &CloneOptions::default(),
+        );
+        assert_eq!(
+            commands(plan),
+            ["cd / && git clone /srv/repo.git gpm-test-repo"]
+        );
+    }
+
+    #[test]
+    fn sparse_lfs_worktree_is_added_without_a_checkout() {
+        let plan = plan_worktree_add(
+            "add".to_string(),
+            Path::new("/repo/.bare"),
+            Path::new("/repo/feature"),
+            "feature",
+            vec!["origin/main".to_string()],
+            &["src".to_string()],
+            &options(),
+        );
+        assert_eq!(
+            commands(Ok(plan)),
+            [
+                "cd /repo/.bare && GIT_LFS_SKIP_SMUDGE=1 git worktree add --no-checkout -b feature \
+                 /repo/feature origin/main",
+                "cd /repo/feature && GIT_LFS_SKIP_SMUDGE=1 git sparse-checkout set --cone src",
+                "cd /repo/feature && GIT_LFS_SKIP_SMUDGE=1 git read-tree -mu HEAD",
+                "cd /repo/feature && git lfs pull",
+            ]
+        );
+    }
+
+    #[test]
+    fn create_dir_only_rolls_back_what_it_created() {
+        let tmp = TempDir::new();
+        let path = tmp.0.join("group/sub/repo");
+        let step = create_dir_step(&path).unwrap();
+        assert_eq!(
+            step.plan(),
+            [
+                format!("mkdir {}", tmp.0.join("group").display()),
+                format!("mkdir {}", tmp.0.join("group/sub").display()),
+                format!("mkdir {}", path.display()),
+            ]
+        );
+
+        step.apply().unwrap();
+        assert!(path.is_dir());
+        step.undo().unwrap();
+        assert!(!tmp.0.join("group").exists());
+        assert!(tmp.0.is_dir());
+    }
+
+    #[test]
+    fn create_dir_rollback_keeps_what_others_added() {
+        let tmp = TempDir::new();
+        let step = create_dir_step(&tmp.0.join("group/repo")).unwrap();
+        step.apply().unwrap();
+        // another job clones next to the repo while this one is running.
+        fs::create_dir(tmp.0.join("group/other")).unwrap();
+
+        assert!(step.undo().is_err());
+        assert!(!tmp.0.join("group/repo").exists());
+        assert!(tmp.0.join("group/other").is_dir());
+    }
+
+    #[test]
+    fn create_dir_leaves_directories_that_already_exist() {
+        let tmp = TempDir::new();
+        let path = tmp.0.join("group/repo");
+        let step = create_dir_step(&path).unwrap();
+        // created by another job between planning and running.
+        fs::create_dir_all(&path).unwrap();
+
+        step.apply().unwrap();
+        step.undo().unwrap();
+        assert!(path.is_dir());
+    }
+}
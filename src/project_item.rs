@@ -1,4 +1,6 @@
-use std::path::PathBuf;
+use std::{collections::HashSet, path::PathBuf};
+
+use tui_tree_widget::TreeItem;
 
 #[derive(Debug, Hash, PartialEq, Eq, Clone)]
 pub struct ProjectItem {
@@ -23,4 +25,34 @@ pub enum ProjectItemType {
     Worktree,
     WorktreeRepo,
     ProjectDirectory,
+    /// a directory inside a project directory that is not a git repo.
+    PlainDirectory,
+    /// an intermediate directory grouping other items, such as the `feature/` in
+    /// `feature/login`.
+    Group,
+}
+
+/// every repo in `forest`, once each even if it is also a standalone project. worktrees are
+/// skipped, as they are part of their repo.
+pub fn forest_repos(forest: &[TreeItem<ProjectItem>]) -> Vec<PathBuf> {
+    let mut seen = HashSet::new();
+    let mut repos = collect_repos(forest);
+    repos.retain(|r| seen.insert(r.clone()));
+    repos
+}
+
+fn collect_repos(forest: &[TreeItem<ProjectItem>]) -> Vec<PathBuf> {
+    let mut repos = vec![];
+    for item in forest.iter() {
+        match item.identifier().project_type {
+            ProjectItemType::WorktreeRepo | ProjectItemType::NonWorktreeRepo => {
+                repos.push(item.identifier().path.clone())
+            }
+            ProjectItemType::ProjectDirectory | ProjectItemType::Group => {
+                repos.extend(collect_repos(item.children()))
+            }
+            ProjectItemType::Worktree | ProjectItemType::PlainDirectory => {}
+        }
+    }
+    repos
 }
@@ -0,0 +1,102 @@
+use std::path::{Path, PathBuf};
+
+use ratatui::{
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Paragraph, StatefulWidget, Widget},
+};
+
+use crate::git::{self, Remote};
+
+const HELP: &str = "a add | d remove | r rename | u set url | b base | p push | esc back";
+
+#[derive(Debug)]
+pub struct RemotesState {
+    /// the repo the remotes belong to.
+    pub repo: PathBuf,
+    remotes: Vec<Remote>,
+    /// the remote new branches are based on, from `checkout.defaultRemote`.
+    base: Option<String>,
+    /// the remote branches are pushed to, from `remote.pushDefault`.
+    push: Option<String>,
+    idx: usize,
+}
+
+impl RemotesState {
+    /// reads the remotes of the repo at `repo`. returns None if git could not list them.
+    pub fn load(repo: &Path) -> Option<Self> {
+        Some(Self {
+            repo: repo.to_path_buf(),
+            remotes: git::list_remotes(repo)?,
+            base: git::config_value(repo, "checkout.defaultRemote"),
+            push: git::config_value(repo, "remote.pushDefault"),
+            idx: 0,
+        })
+    }
+
+    pub fn up(&mut self) {
+        self.idx = self.idx.saturating_sub(1);
+    }
+
+    pub fn down(&mut self) {
+        if self.idx + 1 < self.remotes.len() {
+            self.idx += 1;
+        }
+    }
+
+    pub fn selected(&self) -> Option<&Remote> {
+        self.remotes.get(self.idx)
+    }
+
+    /// the number of lines the view needs, including the help line.
+    pub fn get_line_count(&self) -> usize {
+        self.remotes.len().max(1) + 2
+    }
+
+    fn get_fmt_lines(&self) -> Vec<Line<'_>> {
+        let mut fmt_lines = vec![];
+        if self.remotes.is_empty() {
+            fmt_lines.push(Line::raw("no remotes"));
+        }
+        for (i, remote) in self.remotes.iter().enumerate() {
+            let mut spans = vec![Span::raw(format!("{}  {}", remote.name, remote.url))];
+            if let Some(ref push_url) = remote.push_url {
+                spans.push(Span::raw(format!(" (push: {})", push_url)));
+            }
+            if self.base.as_ref() == Some(&remote.name) {
+                spans.push(Span::styled(" [base]", Style::new().fg(Color::Yellow)));
+            }
+            if self.push.as_ref() == Some(&remote.name) {
+                spans.push(Span::styled(" [push]", Style::new().fg(Color::Yellow)));
+            }
+            let mut line = Line::from(spans);
+            if i == self.idx {
+                line = line.style(Style::default().add_modifier(Modifier::BOLD));
+                line.spans.insert(0, Span::raw(">> "));
+            }
+            fmt_lines.push(line);
+        }
+        fmt_lines.push(Line::default());
+        fmt_lines.push(Line::styled(HELP, Style::new().fg(Color::DarkGray)));
+        fmt_lines
+    }
+}
+
+pub struct RemotesView {}
+
+impl StatefulWidget for RemotesView {
+    type State = RemotesState;
+
+    fn render(
+        self,
+        area: ratatui::prelude::Rect,
+        buf: &mut ratatui::prelude::Buffer,
+        state: &mut Self::State,
+    ) {
+        let lines = state.get_fmt_lines();
+
+        let paragraph = Paragraph::new(lines).block(Block::bordered().title(" Remotes "));
+
+        paragraph.render(area, buf);
+    }
+}
@@ -0,0 +1,170 @@
+use std::path::PathBuf;
+
+use regex::Regex;
+
+use crate::{
+    git,
+    git_url::{GitUrl, GitUrlKind},
+    ops::RepoStep,
+    transaction::Transaction,
+};
+
+/// the modes accepted by `UrlRewrite::new`, for prompts and usage messages.
+pub const MODES: &str = "prefix, regex, to-ssh or to-https";
+
+/// how remote urls are rewritten.
+#[derive(Debug)]
+pub enum UrlRewrite {
+    /// replaces a leading `from` with `to`.
+    Prefix { from: String, to: String },
+    /// replaces every match of the regex, where `$1` etc. in the replacement refer to capture
+    /// groups.
+    Regex { re: Regex, replacement: String },
+    /// switches https links starting with `filter` to scp-style ssh links.
+    ToSsh { filter: String },
+    /// switches ssh links starting with `filter` to https links.
+    ToHttps { filter: String },
+}
+
+impl UrlRewrite {
+    pub fn new(mode: &str, pattern: &str, replacement: &str) -> Result<Self, String> {
+        let pattern = pattern.trim().to_string();
+        let replacement = replacement.trim().to_string();
+        match mode.trim() {
+            "prefix" if pattern.is_empty() => Err("A prefix to replace is needed.".to_string()),
+            "prefix" => Ok(Self::Prefix {
+                from: pattern,
+                to: replacement,
+            }),
+            "regex" => Regex::new(&pattern)
+                .map(|re| Self::Regex { re, replacement })
+                .map_err(|e| format!("Invalid regex: {}", e)),
+            "to-ssh" => Ok(Self::ToSsh { filter: pattern }),
+            "to-https" => Ok(Self::ToHttps { filter: pattern }),
+            other => Err(format!(
+                "'{}' is not a rewrite mode, expected one of {}.",
+                other, MODES
+            )),
+        }
+    }
+
+    /// the rewritten url, or None if the rewrite does not apply to `url` or leaves it unchanged.
+    pub fn apply(&self, url: &str) -> Option<String> {
+        let new_url = match self {
+            Self::Prefix { from, to } => url.strip_prefix(from.as_str()).map(|r| to.clone() + r),
+            Self::Regex { re, replacement } => Some(re.replace_all(url, replacement).into_owned()),
+            Self::ToSsh { filter } if url.starts_with(filter.as_str()) => https_to_ssh(url),
+            Self::ToHttps { filter } if url.starts_with(filter.as_str()) => ssh_to_https(url),
+            Self::ToSsh { .. } | Self::ToHttps { .. } => None,
+        }?;
+        (new_url != url).then_some(new_url)
+    }
+}
+
+fn https_to_ssh(url: &str) -> Option<String> {
+    if !url.starts_with("https://") && !url.starts_with("http://") {
+        return None;
+    }
+    let parsed = GitUrl::parse(url).ok()?;
+    Some(format!(
+        "git@{}:{}{}",
+        parsed.host?,
+        parsed.path,
+        git_suffix(url)
+    ))
+}
+
+fn ssh_to_https(url: &str) -> Option<String> {
+    let parsed = GitUrl::parse(url).ok()?;
+    let is_ssh = match parsed.kind {
+        GitUrlKind::Scp => true,
+        GitUrlKind::Url => url.starts_with("ssh://") || url.contains("+ssh://"),
+        GitUrlKind::File | GitUrlKind::Local => false,
+    };
+    if !is_ssh {
+        return None;
+    }
+    Some(format!(
+        "https://{}/{}{}",
+        parsed.host?,
+        parsed.path,
+        git_suffix(url)
+    ))
+}
+
+/// keeps the `.git` suffix of the original url, which `GitUrl` strips.
+fn git_suffix(url: &str) -> &'static str {
+    if url.trim_end_matches('/').ends_with(".git") {
+        ".git"
+    } else {
+        ""
+    }
+}
+
+/// a single remote url that will be rewritten.
+#[derive(Debug, Clone)]
+pub struct RemoteChange {
+    pub repo: PathBuf,
+    pub remote: String,
+    /// set if this is the push url of a remote that pushes somewhere other than it fetches from.
+    pub push: bool,
+    pub old_url: String,
+    pub new_url: String,
+}
+
+/// finds every remote url of `repos` that `rewrite` changes.
+pub fn plan(repos: &[PathBuf], rewrite: &UrlRewrite) -> Vec<RemoteChange> {
+    let mut changes = vec![];
+    for repo in repos.iter() {
+        for remote in git::list_remotes(repo).unwrap_or_default() {
+            let urls = [(false, Some(remote.url)), (true, remote.push_url)];
+            for (push, url) in urls {
+                let Some(old_url) = url else {
+                    continue;
+                };
+                if let Some(new_url) = rewrite.apply(&old_url) {
+                    changes.push(RemoteChange {
+                        repo: repo.clone(),
+                        remote: remote.name.clone(),
+                        push,
+                        old_url,
+                        new_url,
+                    });
+                }
+            }
+        }
+    }
+    changes
+}
+
+/// the transaction rewriting the urls in `changes`. if any of them cannot be rewritten, those
+/// already rewritten are put back.
+pub fn to_transaction(changes: &[RemoteChange]) -> Transaction<RepoStep> {
+    let steps = changes
+        .iter()
+        .map(|c| RepoStep::SetRemoteUrl {
+            repo: c.repo.clone(),
+            remote: c.remote.clone(),
+            push: c.push,
+            old_url: c.old_url.clone(),
+            new_url: c.new_url.clone(),
+        })
+        .collect();
+    // the rewrite is recorded against the directory holding every repo it changes.
+    let target = changes
+        .iter()
+        .map(|c| c.repo.clone())
+        .reduce(|a, b| {
+            a.components()
+                .zip(b.components())
+                .take_while(|(x, y)| x == y)
+                .map(|(x, _)| x)
+                .collect()
+        })
+        .unwrap_or_default();
+    Transaction::new(
+        format!("Rewrite {} remote urls", changes.len()),
+        target,
+        steps,
+    )
+}
@@ -1,4 +1,5 @@
 #[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+#[allow(clippy::enum_variant_names)]
 pub enum Screen {
     #[default]
     Main,
@@ -9,5 +10,23 @@ pub enum Screen {
     WorktreeRepoDelete,
     WorktreeCreate,
     WorktreeDelete,
+    PlainDirectoryInit,
+    PlainDirectoryConvert,
+    ConvertToWorktreeRepo,
+    ConvertToNormalRepo,
+    DissociateFromCache,
+    SubmoduleUpdate,
+    LfsFetch,
+    ForkRepoCreate,
+    Remotes,
+    RemoteAdd,
+    RemoteRename,
+    RemoteSetUrl,
+    RemoteRemove,
+    RewriteRemotes,
+    RewriteRemotesPreview,
+    Jobs,
+    JobLog,
+    History,
     Summary,
 }
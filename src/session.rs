@@ -0,0 +1,36 @@
+use std::{fs, path::PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config;
+
+/// what was opened and selected in the project tree, kept between runs. items are kept by path,
+/// since the rest of what identifies them, such as whether they have changes, may differ.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Session {
+    pub opened: Vec<PathBuf>,
+    pub selected: Option<PathBuf>,
+}
+
+fn session_file() -> Option<PathBuf> {
+    config::state_dir().map(|d| d.join("session.json"))
+}
+
+/// the session saved by the last run, or an empty one if there is none or it cannot be read.
+pub fn load() -> Session {
+    session_file()
+        .and_then(|p| fs::read_to_string(p).ok())
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+pub fn save(session: &Session) -> Result<(), String> {
+    let Some(path) = session_file() else {
+        return Err("Could not find a state directory for gpm.".to_string());
+    };
+    let contents = serde_json::to_string(session).map_err(|e| e.to_string())?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    fs::write(&path, contents).map_err(|e| format!("Failed to write to {:?}: {}", path, e))
+}
@@ -0,0 +1,450 @@
+use std::{
+    io::{self, Write},
+    path::PathBuf,
+};
+
+use ratatui::{
+    layout::{Constraint, Layout},
+    style::{Color, Modifier, Style},
+    text::Line,
+    widgets::{
+        Block, Paragraph, Scrollbar, ScrollbarOrientation, ScrollbarState, StatefulWidget, Widget,
+    },
+};
+
+use unicode_width::UnicodeWidthChar;
+
+use crate::{exec::CommandRecord, screen::Screen};
+
+/// how far page up and page down scroll.
+const PAGE: usize = 10;
+
+/// the outcome of an operation, or why it could not be run, with the output of every command it
+/// ran.
+#[derive(Debug)]
+pub struct SummaryState {
+    lines: Vec<String>,
+    commands: Vec<CommandRecord>,
+    /// which commands have their output shown.
+    expanded: Vec<bool>,
+    /// the command that enter expands or collapses.
+    selected: usize,
+    /// how many lines the view is scrolled down.
+    scroll: usize,
+    /// set when the selected command has changed, so the next render scrolls to it.
+    follow_selected: bool,
+    /// the project the operation changed, which can be opened from the summary.
+    pub target: Option<PathBuf>,
+    /// whether the operation failed and can be run again.
+    pub retryable: bool,
+    /// the screen the summary goes back to when it is dismissed.
+    pub back: Screen,
+    /// feedback for the last key pressed, e.g. that the output was copied.
+    message: Option<String>,
+}
+
+impl SummaryState {
+    pub fn new(lines: Vec<String>) -> Self {
+        Self {
+            lines,
+            commands: vec![],
+            expanded: vec![],
+            selected: 0,
+            scroll: 0,
+            follow_selected: false,
+            target: None,
+            retryable: false,
+            back: Screen::Main,
+            message: None,
+        }
+    }
+
+    /// shows the output of `commands`. the output of those that failed starts expanded.
+    pub fn with_commands(mut self, commands: Vec<CommandRecord>) -> Self {
+        self.expanded = commands.iter().map(|c| c.exit_code != Some(0)).collect();
+        self.commands = commands;
+        self
+    }
+
+    pub fn with_target(mut self, target: PathBuf) -> Self {
+        self.target = Some(target);
+        self
+    }
+
+    pub fn retryable(mut self, retryable: bool) -> Self {
+        self.retryable = retryable;
+        self
+    }
+
+    pub fn back_to(mut self, screen: Screen) -> Self {
+        self.back = screen;
+        self
+    }
+
+    pub fn scroll_down(&mut self, by: usize) {
+        self.scroll += by;
+    }
+
+    pub fn scroll_up(&mut self, by: usize) {
+        self.scroll = self.scroll.saturating_sub(by);
+    }
+
+    pub fn page_down(&mut self) {
+        self.scroll_down(PAGE);
+    }
+
+    pub fn page_up(&mut self) {
+        self.scroll_up(PAGE);
+    }
+
+    pub fn next_command(&mut self) {
+        if self.selected + 1 < self.commands.len() {
+            self.selected += 1;
+        }
+        self.follow_selected = true;
+    }
+
+    pub fn previous_command(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+        self.follow_selected = true;
+    }
+
+    /// shows or hides the output of the selected command.
+    pub fn toggle_selected(&mut self) {
+        if let Some(expanded) = self.expanded.get_mut(self.selected) {
+            *expanded = !*expanded;
+            self.follow_selected = true;
+        }
+    }
+
+    /// copies the summary and the output of every command to the clipboard.
+    pub fn copy(&mut self) {
+        let text = self.plain_text();
+        self.message = Some(match copy_to_clipboard(&text) {
+            Ok(()) => format!("copied {} lines", text.lines().count()),
+            Err(e) => format!("could not copy: {}", e),
+        });
+    }
+
+    pub fn clear_message(&mut self) {
+        self.message = None;
+    }
+
+    /// everything in the summary as plain text, with all output expanded.
+    fn plain_text(&self) -> String {
+        let mut text = self.lines.join("\n");
+        for c in self.commands.iter() {
+            text.push_str(&format!("\n\n$ {}  [{}]", c.command_line(), exit_text(c)));
+            for (name, output) in [("stdout", &c.stdout), ("stderr", &c.stderr)] {
+                if !output.is_empty() {
+                    text.push_str(&format!("\n{}:\n{}", name, output));
+                }
+            }
+        }
+        text.push('\n');
+        text
+    }
+
+    /// every line to show before wrapping, with the command each belongs to, if any.
+    fn styled_lines(&self) -> Vec<(String, Style, Option<usize>)> {
+        let mut lines = vec![];
+        for l in self.lines.iter() {
+            let (text, style) = styled_summary_line(l);
+            for (i, part) in text.lines().enumerate() {
+                // the continuation lines of a multi-line error line up under its first line.
+                let part = if i == 0 {
+                    part.to_string()
+                } else {
+                    format!("    {}", part)
+                };
+                lines.push((part, style, None));
+            }
+        }
+        if let Some(ref target) = self.target {
+            lines.push((
+                format!("in {}", target.to_string_lossy()),
+                Style::new().fg(Color::DarkGray),
+                None,
+            ));
+        }
+        if self.commands.is_empty() {
+            return lines;
+        }
+
+        lines.push((String::new(), Style::new(), None));
+        lines.push((
+            format!("Commands ({})", self.commands.len()),
+            Style::new().add_modifier(Modifier::BOLD),
+            None,
+        ));
+        for (i, c) in self.commands.iter().enumerate() {
+            let expanded = self.expanded[i];
+            let colour = if c.exit_code == Some(0) {
+                Color::Yellow
+            } else {
+                Color::LightRed
+            };
+            let mut header_style = Style::new().fg(colour);
+            if i == self.selected {
+                header_style = header_style.add_modifier(Modifier::REVERSED);
+            }
+            lines.push((
+                format!(
+                    "{} $ {}  [{}]",
+                    if expanded { "▾" } else { "▸" },
+                    c.command_line(),
+                    exit_text(c)
+                ),
+                header_style,
+                Some(i),
+            ));
+            if !expanded {
+                continue;
+            }
+            if c.stdout.is_empty() && c.stderr.is_empty() {
+                lines.push((
+                    "    (no output)".to_string(),
+                    Style::new().fg(Color::DarkGray),
+                    Some(i),
+                ));
+            }
+            for (name, output) in [("stdout", &c.stdout), ("stderr", &c.stderr)] {
+                if output.is_empty() {
+                    continue;
+                }
+                lines.push((
+                    format!("  {}:", name),
+                    Style::new().fg(Color::DarkGray),
+                    Some(i),
+                ));
+                lines.extend(
+                    output
+                        .lines()
+                        .map(|l| (format!("    {}", l), Style::new(), Some(i))),
+                );
+            }
+        }
+        lines
+    }
+
+    fn help(&self) -> String {
+        let mut keys = vec!["j/k scroll"];
+        if !self.commands.is_empty() {
+            keys.extend(["tab next command", "enter expand"]);
+        }
+        keys.push("c copy");
+        if self.retryable {
+            keys.push("r retry");
+        }
+        if self.target.is_some() {
+            keys.push("o open project");
+        }
+        keys.push("esc back");
+        keys.join(" | ")
+    }
+}
+
+/// the text of a summary line with its marker replaced by an icon, and the style to show it in.
+fn styled_summary_line(line: &str) -> (String, Style) {
+    let bold = Style::new().add_modifier(Modifier::BOLD);
+    let markers = [
+        ("SUCCESS: ", "✔ ", bold.fg(Color::LightGreen)),
+        ("FAILURE: ", "✘ ", bold.fg(Color::LightRed)),
+        ("CANCELLED: ", "✘ cancelled: ", bold.fg(Color::LightRed)),
+        ("DRY RUN: ", "dry run: ", bold.fg(Color::Yellow)),
+        ("DONE: ", "  ✔ ", Style::new().fg(Color::LightGreen)),
+        ("FAILED: ", "  ✘ ", Style::new().fg(Color::LightRed)),
+        ("Error: ", "    ", Style::new().fg(Color::LightRed)),
+        (
+            "NOT RUN: ",
+            "  · not run: ",
+            Style::new().fg(Color::DarkGray),
+        ),
+        (
+            "ROLLED BACK: ",
+            "  ↶ rolled back: ",
+            Style::new().fg(Color::Yellow),
+        ),
+        (
+            "ROLLBACK FAILED: ",
+            "  ⚠ rollback failed: ",
+            Style::new().fg(Color::LightRed),
+        ),
+        ("WARNING: ", "⚠ ", Style::new().fg(Color::Yellow)),
+    ];
+    for (marker, icon, style) in markers {
+        if let Some(rest) = line.strip_prefix(marker) {
+            return (format!("{}{}", icon, rest), style);
+        }
+    }
+    (line.to_string(), Style::new())
+}
+
+fn exit_text(c: &CommandRecord) -> String {
+    match c.exit_code {
+        Some(code) => format!("exit {}", code),
+        None => "killed".to_string(),
+    }
+}
+
+/// splits `line` into pieces no wider than `width` columns on screen, so that e.g. CJK text,
+/// whose characters take two columns, is not cut off. a character wider than `width` gets a piece
+/// of its own.
+fn wrap(line: &str, width: usize) -> Vec<String> {
+    if line.is_empty() || width == 0 {
+        return vec![line.to_string()];
+    }
+    let mut pieces = vec![];
+    let mut piece = String::new();
+    let mut piece_width = 0;
+    for c in line.chars() {
+        let w = c.width().unwrap_or(0);
+        if piece_width + w > width && !piece.is_empty() {
+            pieces.push(std::mem::take(&mut piece));
+            piece_width = 0;
+        }
+        piece.push(c);
+        piece_width += w;
+    }
+    pieces.push(piece);
+    pieces
+}
+
+/// sets the terminal's clipboard with an OSC 52 escape sequence, which also works over ssh.
+fn copy_to_clipboard(text: &str) -> io::Result<()> {
+    let mut stdout = io::stdout();
+    write!(stdout, "\x1b]52;c;{}\x07", base64(text.as_bytes()))?;
+    stdout.flush()
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+pub struct SummaryView {}
+
+impl StatefulWidget for SummaryView {
+    type State = SummaryState;
+
+    fn render(
+        self,
+        area: ratatui::prelude::Rect,
+        buf: &mut ratatui::prelude::Buffer,
+        state: &mut Self::State,
+    ) {
+        let block = Block::bordered().title(" Summary ");
+        let inner = block.inner(area);
+        block.render(area, buf);
+        let [text_area, help_area] =
+            Layout::vertical([Constraint::Fill(1), Constraint::Length(1)]).areas(inner);
+
+        // lines are wrapped here rather than by the paragraph, so that scrolling is by the lines
+        // actually shown. one column is left for the scrollbar.
+        let width = text_area.width.saturating_sub(1) as usize;
+        let mut lines = vec![];
+        let mut selected_at = None;
+        for (text, style, command) in state.styled_lines() {
+            if command == Some(state.selected) && selected_at.is_none() {
+                selected_at = Some(lines.len());
+            }
+            lines.extend(
+                wrap(&text, width)
+                    .into_iter()
+                    .map(|l| Line::styled(l, style)),
+            );
+        }
+
+        let height = text_area.height as usize;
+        if state.follow_selected {
+            if let Some(at) = selected_at
+                && (at < state.scroll || at >= state.scroll + height)
+            {
+                state.scroll = at;
+            }
+            state.follow_selected = false;
+        }
+        let max_scroll = lines.len().saturating_sub(height);
+        state.scroll = state.scroll.min(max_scroll);
+
+        let shown: Vec<Line> = lines
+            .iter()
+            .skip(state.scroll)
+            .take(height)
+            .cloned()
+            .collect();
+        Paragraph::new(shown).render(text_area, buf);
+        if max_scroll > 0 {
+            let mut scrollbar_state = ScrollbarState::new(max_scroll).position(state.scroll);
+            Scrollbar::new(ScrollbarOrientation::VerticalRight)
+                .begin_symbol(None)
+                .end_symbol(None)
+                .render(text_area, buf, &mut scrollbar_state);
+        }
+
+        let help = match state.message {
+            Some(ref message) => Line::styled(message.clone(), Style::new().fg(Color::Yellow)),
+            None => Line::styled(state.help(), Style::new().fg(Color::DarkGray)),
+        };
+        Paragraph::new(help).render(help_area, buf);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base64_pads_to_whole_groups() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foob"), "Zm9vYg==");
+        assert_eq!(base64(b"fooba"), "Zm9vYmE=");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+    }
+
+    #[test]
+    fn base64_uses_the_whole_alphabet() {
+        assert_eq!(base64(&[0xfb, 0xff, 0xbf]), "+/+/");
+        assert_eq!(base64("é".as_bytes()), "w6k=");
+    }
+
+    #[test]
+    fn wrap_splits_ascii_by_width() {
+        assert_eq!(wrap("abcdefg", 3), ["abc", "def", "g"]);
+        assert_eq!(wrap("abc", 3), ["abc"]);
+        assert_eq!(wrap("", 3), [""]);
+        assert_eq!(wrap("abc", 0), ["abc"]);
+    }
+
+    #[test]
+    fn wrap_counts_wide_characters_as_two_columns() {
+        assert_eq!(
+            wrap("日本語のテキスト", 5),
+            ["日本", "語の", "テキ", "スト"]
+        );
+        assert_eq!(wrap("a日本", 4), ["a日", "本"]);
+        assert_eq!(wrap("日本", 1), ["日", "本"]);
+    }
+
+    #[test]
+    fn wrap_keeps_combining_marks_with_their_letter() {
+        assert_eq!(wrap("e\u{301}e\u{301}", 1), ["e\u{301}", "e\u{301}"]);
+    }
+}
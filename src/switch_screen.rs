@@ -39,19 +39,19 @@ impl ScreenSwitcherState {
         self.options[self.idx].1
     }
 
-    fn get_fmt_lines(&self) -> Vec<Line> {
+    fn get_fmt_lines(&self) -> Vec<Line<'_>> {
         let mut fmt_lines = vec![];
         for (i, opt) in self.options.iter().map(|(o, _)| o).enumerate() {
-            if i == self.idx as usize {
+            if i == self.idx {
                 fmt_lines.push(Line::styled(
                     format!(">> {} <<", opt),
                     Style::default().add_modifier(Modifier::BOLD),
                 ));
             } else {
-                fmt_lines.push(Line::raw(format!("{}", opt)));
+                fmt_lines.push(Line::raw(opt.to_string()));
             }
         }
-        return fmt_lines;
+        fmt_lines
     }
 
     pub fn get_options_count(&self) -> usize {
@@ -93,7 +93,7 @@ impl ScreenSwitcherStateBuilder {
 
     pub fn with_option(mut self, text: String, target_screen: Screen) -> Self {
         self.options.push((text, target_screen));
-        return self;
+        self
     }
 
     pub fn build(self) -> ScreenSwitcherState {
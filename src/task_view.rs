@@ -0,0 +1,100 @@
+use ratatui::{
+    layout::{Constraint, Layout},
+    style::{Color, Modifier, Style},
+    text::Line,
+    widgets::{Block, Gauge, Paragraph, StatefulWidget, Widget},
+};
+
+use crate::exec::Task;
+
+const SPINNER: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+
+/// shows a task's progress and the tail of its output, followed by its summary once it has
+/// finished.
+pub struct TaskView<'a> {
+    /// how many lines the log is scrolled up from its end.
+    pub scroll: usize,
+    pub summary: &'a [String],
+}
+
+impl StatefulWidget for TaskView<'_> {
+    type State = Task;
+
+    fn render(
+        self,
+        area: ratatui::prelude::Rect,
+        buf: &mut ratatui::prelude::Buffer,
+        state: &mut Self::State,
+    ) {
+        let block = Block::bordered()
+            .title(format!(" {} ", state.title))
+            .title_bottom(if state.is_finished() {
+                " r retry | j/k scroll | esc back "
+            } else {
+                " Ctrl-C to cancel | j/k scroll | esc back "
+            });
+        let inner = block.inner(area);
+        block.render(area, buf);
+
+        let percent = state.percent().filter(|_| !state.is_finished());
+        let [status_area, gauge_area, log_area] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Length(if percent.is_some() { 1 } else { 0 }),
+            Constraint::Fill(1),
+        ])
+        .areas(inner);
+
+        let elapsed = state.elapsed();
+        let status = if state.is_finished() {
+            format!("Finished in {}s", elapsed.as_secs())
+        } else if state.is_cancelled() {
+            format!("Cancelling... {}s", elapsed.as_secs())
+        } else {
+            let frame = SPINNER[(elapsed.as_millis() / 100) as usize % SPINNER.len()];
+            format!(
+                "{} {}s  {}",
+                frame,
+                elapsed.as_secs(),
+                state.progress.as_deref().unwrap_or("")
+            )
+        };
+        Paragraph::new(Line::styled(
+            status,
+            Style::default().add_modifier(Modifier::BOLD),
+        ))
+        .render(status_area, buf);
+
+        if let Some(percent) = percent {
+            Gauge::default()
+                .gauge_style(Style::new().fg(Color::LightGreen))
+                .percent(percent)
+                .render(gauge_area, buf);
+        }
+
+        // the log follows its end unless it has been scrolled up.
+        let mut lines: Vec<Line> = state
+            .log
+            .iter()
+            .map(|l| {
+                if l.starts_with("$ ") {
+                    Line::styled(l.as_str(), Style::new().fg(Color::Yellow))
+                } else {
+                    Line::raw(l.as_str())
+                }
+            })
+            .collect();
+        if !self.summary.is_empty() {
+            lines.push(Line::default());
+            lines.extend(
+                self.summary.iter().map(|l| {
+                    Line::styled(l.as_str(), Style::default().add_modifier(Modifier::BOLD))
+                }),
+            );
+        }
+        let height = log_area.height as usize;
+        let end = lines.len().saturating_sub(self.scroll);
+        let start = end.saturating_sub(height);
+        let lines = lines.drain(start..end).collect::<Vec<_>>();
+        Paragraph::new(lines).render(log_area, buf);
+    }
+}
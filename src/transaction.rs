@@ -0,0 +1,159 @@
+use std::path::PathBuf;
+
+use crate::{
+    exec,
+    history::{self, StepRecord},
+};
+
+/// a single change made by a transaction, which can be undone if a later step fails.
+pub trait Step {
+    fn describe(&self) -> String;
+    /// the exact commands `apply` runs and the files it changes, for a dry run.
+    fn plan(&self) -> Vec<String>;
+    fn apply(&self) -> Result<(), String>;
+    /// reverses `apply`. steps whose effects are removed along with an earlier step, e.g. commands
+    /// run inside a directory that is deleted, can do nothing.
+    fn undo(&self) -> Result<(), String>;
+    /// the repos, mirrors and other directories the step changes, besides the transaction's
+    /// target, so that jobs changing the same ones are not run at once.
+    fn keys(&self) -> Vec<PathBuf>;
+}
+
+/// an ordered set of steps that either all happen, or are all undone, which can be previewed
+/// before running.
+#[derive(Debug, Clone)]
+pub struct Transaction<S> {
+    pub title: String,
+    /// the project the transaction changes, recorded in the history.
+    pub target: PathBuf,
+    steps: Vec<S>,
+}
+
+impl<S: Step> Transaction<S> {
+    pub fn new(title: String, target: PathBuf, steps: Vec<S>) -> Self {
+        Self {
+            title,
+            target,
+            steps,
+        }
+    }
+
+    pub fn push(&mut self, step: S) {
+        self.steps.push(step);
+    }
+
+    /// the target and every directory the steps change.
+    pub fn keys(&self) -> Vec<PathBuf> {
+        let mut keys = vec![self.target.clone()];
+        for key in self.steps.iter().flat_map(|s| s.keys()) {
+            if !keys.contains(&key) {
+                keys.push(key);
+            }
+        }
+        keys
+    }
+
+    /// describes every step the transaction will take, without changing anything.
+    pub fn preview(&self) -> Vec<String> {
+        self.steps
+            .iter()
+            .enumerate()
+            .map(|(i, s)| format!("{}. {}", i + 1, s.describe()))
+            .collect()
+    }
+
+    /// lists every command the transaction would run and every file it would change, in the
+    /// directory it would run in, without doing any of it.
+    pub fn dry_run(&self) -> Vec<String> {
+        let mut lines = vec![format!("DRY RUN: {}", self.title)];
+        for (i, step) in self.steps.iter().enumerate() {
+            lines.push(format!("{}. {}", i + 1, step.describe()));
+            lines.extend(step.plan().into_iter().map(|l| format!("   {}", l)));
+        }
+        lines
+    }
+
+    /// runs each step in order. if a step fails, the completed steps are undone in reverse order.
+    /// both the success and failure cases carry a summary line for every step, saying whether it
+    /// was done, failed, was not run or was rolled back. the outcome, every step and every command
+    /// run are appended to the history.
+    pub fn run(&self) -> Result<Vec<String>, Vec<String>> {
+        // what a step changes is worked out before it runs, since e.g. the directories it creates
+        // are no longer missing once it has.
+        let mut records: Vec<StepRecord> = self
+            .steps
+            .iter()
+            .map(|s| StepRecord {
+                description: s.describe(),
+                changes: s.plan(),
+                outcome: "NOT RUN".to_string(),
+            })
+            .collect();
+        let (mut result, commands) = exec::recording(|| self.run_steps(&mut records));
+        if let Err(e) = history::record(&self.title, &self.target, &result, commands, records) {
+            let (Ok(summary) | Err(summary)) = &mut result;
+            summary.push(format!(
+                "WARNING: could not record this in the history: {}",
+                e
+            ));
+        }
+        result
+    }
+
+    /// runs the steps like `run`, without appending them to the history.
+    #[cfg(test)]
+    pub fn run_unrecorded(&self) -> Result<Vec<String>, Vec<String>> {
+        let mut records: Vec<StepRecord> = self
+            .steps
+            .iter()
+            .map(|s| StepRecord {
+                description: s.describe(),
+                changes: vec![],
+                outcome: "NOT RUN".to_string(),
+            })
+            .collect();
+        self.run_steps(&mut records)
+    }
+
+    /// runs the steps, setting the outcome of each in `records`.
+    fn run_steps(&self, records: &mut [StepRecord]) -> Result<Vec<String>, Vec<String>> {
+        let mut summary = vec![];
+
+        for (i, step) in self.steps.iter().enumerate() {
+            if let Err(e) = step.apply() {
+                records[i].outcome = "FAILED".to_string();
+                summary.insert(0, format!("FAILURE: {}", self.title));
+                summary.push(format!("FAILED: {}", step.describe()));
+                summary.push(format!("Error: {}", e));
+                for skipped in self.steps[i + 1..].iter() {
+                    summary.push(format!("NOT RUN: {}", skipped.describe()));
+                }
+                // the rollback has to happen even if the failure was the task being cancelled.
+                exec::ignoring_cancel(|| {
+                    for (done, record) in self.steps[..i].iter().zip(records.iter_mut()).rev() {
+                        match done.undo() {
+                            Ok(()) => {
+                                record.outcome = "ROLLED BACK".to_string();
+                                summary.push(format!("ROLLED BACK: {}", done.describe()));
+                            }
+                            Err(e) => {
+                                record.outcome = "ROLLBACK FAILED".to_string();
+                                summary.push(format!(
+                                    "ROLLBACK FAILED: {} ({})",
+                                    done.describe(),
+                                    e
+                                ));
+                            }
+                        }
+                    }
+                });
+                return Err(summary);
+            }
+            records[i].outcome = "DONE".to_string();
+            summary.push(format!("DONE: {}", step.describe()));
+        }
+
+        summary.insert(0, format!("SUCCESS: {}", self.title));
+        Ok(summary)
+    }
+}
@@ -0,0 +1,144 @@
+use std::{
+    collections::HashSet,
+    path::PathBuf,
+    sync::mpsc::{self, Receiver},
+    time::{Duration, Instant},
+};
+
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher, event::ModifyKind};
+use tui_tree_widget::TreeItem;
+
+use crate::{
+    layout::RepoLayout,
+    project_item::{ProjectItem, ProjectItemType},
+};
+
+/// how long the projects have to stay unchanged before the tree is rebuilt, so that e.g. a clone
+/// only causes one rebuild.
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// watches the directories that decide the shape of the project tree, such as project
+/// directories and the worktree admin directories of repos.
+pub struct ProjectWatcher {
+    watcher: RecommendedWatcher,
+    events: Receiver<notify::Result<Event>>,
+    watched: HashSet<PathBuf>,
+    /// the git directories being watched, in which only the `worktrees` directory appearing or
+    /// disappearing changes the tree.
+    git_dirs: HashSet<PathBuf>,
+    /// the paths changed since the tree was last rebuilt.
+    changed: Vec<PathBuf>,
+    last_change: Option<Instant>,
+}
+
+impl std::fmt::Debug for ProjectWatcher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ProjectWatcher")
+            .field("watched", &self.watched)
+            .field("changed", &self.changed)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ProjectWatcher {
+    pub fn new() -> notify::Result<Self> {
+        let (tx, rx) = mpsc::channel();
+        Ok(Self {
+            watcher: notify::recommended_watcher(tx)?,
+            events: rx,
+            watched: HashSet::new(),
+            git_dirs: HashSet::new(),
+            changed: vec![],
+            last_change: None,
+        })
+    }
+
+    /// watches the directories of the items in `forest`, and stops watching any that are no
+    /// longer in it.
+    pub fn watch(&mut self, forest: &[TreeItem<ProjectItem>]) {
+        let mut paths = HashSet::new();
+        self.git_dirs.clear();
+        watch_paths(forest, &mut paths, &mut self.git_dirs);
+        paths.extend(self.git_dirs.iter().cloned());
+
+        for gone in self.watched.difference(&paths) {
+            let _ = self.watcher.unwatch(gone);
+        }
+        // directories that cannot be watched, e.g. because they have just been deleted, are
+        // left out and tried again next time.
+        self.watched = paths
+            .into_iter()
+            .filter(|p| {
+                self.watched.contains(p)
+                    || self.watcher.watch(p, RecursiveMode::NonRecursive).is_ok()
+            })
+            .collect();
+    }
+
+    /// the paths that have changed, once nothing has changed for a while.
+    pub fn poll(&mut self) -> Option<Vec<PathBuf>> {
+        while let Ok(event) = self.events.try_recv() {
+            let Ok(event) = event else {
+                continue;
+            };
+            // only directories appearing, disappearing or being renamed change the tree.
+            // changes to files, e.g. git updating the index, are left out.
+            if !matches!(
+                event.kind,
+                EventKind::Create(_)
+                    | EventKind::Remove(_)
+                    | EventKind::Modify(ModifyKind::Name(_))
+            ) {
+                continue;
+            }
+            let before = self.changed.len();
+            self.changed.extend(event.paths.into_iter().filter(|p| {
+                p.parent().is_none_or(|dir| !self.git_dirs.contains(dir))
+                    || p.file_name().is_some_and(|n| n == "worktrees")
+            }));
+            if self.changed.len() > before {
+                self.last_change = Some(Instant::now());
+            }
+        }
+        if self.last_change?.elapsed() < DEBOUNCE {
+            return None;
+        }
+        self.last_change = None;
+        Some(std::mem::take(&mut self.changed))
+    }
+}
+
+/// the directories whose contents decide which items are under those in `items`. the git
+/// directories of repos go in `git_dirs`, since their `worktrees` directory cannot be watched
+/// until a worktree has been added.
+fn watch_paths(
+    items: &[TreeItem<ProjectItem>],
+    paths: &mut HashSet<PathBuf>,
+    git_dirs: &mut HashSet<PathBuf>,
+) {
+    for item in items {
+        let id = item.identifier();
+        match id.project_type {
+            ProjectItemType::ProjectDirectory
+            | ProjectItemType::Group
+            | ProjectItemType::PlainDirectory => {
+                paths.insert(id.path.clone());
+            }
+            ProjectItemType::WorktreeRepo => {
+                if let Some(layout) = RepoLayout::detect(&id.path) {
+                    let git_dir = layout.bare_dir(&id.path);
+                    paths.insert(layout.worktree_root(&id.path, None));
+                    paths.insert(git_dir.join("worktrees"));
+                    git_dirs.insert(git_dir);
+                }
+            }
+            ProjectItemType::NonWorktreeRepo => {
+                let git_dir = id.path.join(".git");
+                paths.insert(git_dir.join("worktrees"));
+                git_dirs.insert(git_dir);
+            }
+            ProjectItemType::Worktree => {}
+        }
+        watch_paths(item.children(), paths, git_dirs);
+    }
+}